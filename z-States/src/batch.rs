@@ -0,0 +1,173 @@
+// Headless batch runs. Every run builds its own building from a preset and a
+// seed, so runs share nothing and can be spread over any number of threads;
+// results always come back in job order, which keeps the report identical
+// whatever the thread count.
+
+use std::thread;
+
+use crate::building::{Building, GroupTraffic};
+use crate::dispatch::{Dispatcher, EtaWeights};
+use crate::events;
+use crate::timing::Timing;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrafficProfile {
+    Light,
+    Normal,
+    Heavy,
+}
+
+impl TrafficProfile {
+    pub const ALL: [TrafficProfile; 3] = [TrafficProfile::Light, TrafficProfile::Normal, TrafficProfile::Heavy];
+
+    pub fn arrivals_per_minute(&self) -> f64 {
+        match self {
+            TrafficProfile::Light => 4.0,
+            TrafficProfile::Normal => 8.0,
+            TrafficProfile::Heavy => 16.0,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TrafficProfile> {
+        TrafficProfile::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrafficProfile::Light => "light",
+            TrafficProfile::Normal => "normal",
+            TrafficProfile::Heavy => "heavy",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub name: String,
+    pub dispatcher: Dispatcher,
+    pub traffic: TrafficProfile,
+    pub parking: Option<bool>,  // None keeps the building's own parking setting
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RunResult {
+    pub seed: u64,
+    pub average_wait: f32,  // Seconds
+    pub max_wait: f32,
+    pub energy_kwh: f32,
+    pub served: usize,
+}
+
+pub struct BatchConfig {
+    pub seeds: u64,
+    pub base_seed: u64,  // Run i of every scenario uses seed base_seed + i, so scenarios see the same arrivals
+    pub steps: usize,
+    pub threads: usize,
+    pub timing: Timing,
+    pub groups: GroupTraffic,
+    pub events: bool,   // Run on the discrete-event engine instead of stepping every tick
+    pub per_run: bool,  // Also print one line per run
+}
+
+// Runs `run` on every job over up to `threads` threads, results in job order
+pub fn run_parallel<J, R, F>(jobs: &[J], threads: usize, run: F) -> Vec<R>
+where
+    J: Sync,
+    R: Send,
+    F: Fn(&J) -> R + Sync,
+{
+    let chunk_size = jobs.len().div_ceil(threads.max(1)).max(1);
+    let run = &run;
+    thread::scope(|scope| {
+        let handles: Vec<_> = jobs
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(run).collect::<Vec<_>>()))
+            .collect();
+        handles.into_iter().flat_map(|h| h.join().expect("simulation thread panicked")).collect()
+    })
+}
+
+pub fn run_once(mut building: Building, seed: u64, steps: usize, events: bool) -> RunResult {
+    if events {
+        events::run(&mut building, steps as u64);
+    } else {
+        for _ in 0..steps {
+            building.step();
+        }
+    }
+    let (average_wait, max_wait) = building.wait_stats();
+    RunResult {
+        seed,
+        average_wait,
+        max_wait,
+        energy_kwh: building.total_energy_kwh(),
+        served: building.elevators().map(|e| e.passengers_served).sum(),
+    }
+}
+
+// Results per scenario, each in seed order
+pub fn run_scenarios(build: fn() -> Building, scenarios: &[Scenario], config: &BatchConfig) -> Vec<Vec<RunResult>> {
+    let jobs: Vec<(usize, u64)> = (0..scenarios.len())
+        .flat_map(|s| (0..config.seeds).map(move |i| (s, config.base_seed + i)))
+        .collect();
+    let results = run_parallel(&jobs, config.threads, |&(s, seed)| {
+        let mut building = build().with_seed(seed);
+        building.set_timing(config.timing);
+        building.set_groups(config.groups);
+        building.set_dispatcher(scenarios[s].dispatcher);
+        building.arrivals_per_minute = scenarios[s].traffic.arrivals_per_minute();
+        if let Some(parking) = scenarios[s].parking {
+            building.set_parking(parking);
+        }
+        run_once(building, seed, config.steps, config.events)
+    });
+    results.chunks(config.seeds.max(1) as usize).map(|runs| runs.to_vec()).collect()
+}
+
+fn mean(values: impl Iterator<Item = f32>) -> f32 {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    if count == 0 { 0.0 } else { sum / count as f32 }
+}
+
+// Nearest car and ETA (with the given weights) under every traffic profile
+pub fn default_scenarios(eta_weights: EtaWeights) -> Vec<Scenario> {
+    let mut scenarios = Vec::new();
+    for traffic in TrafficProfile::ALL {
+        for (name, dispatcher) in [("nearest", Dispatcher::Nearest), ("eta", Dispatcher::Eta(eta_weights))] {
+            scenarios.push(Scenario { name: name.to_string(), dispatcher, traffic, parking: None });
+        }
+    }
+    scenarios
+}
+
+pub fn run(build: fn() -> Building, scenarios: &[Scenario], config: &BatchConfig) {
+    println!(
+        "Running {} scenarios x {} seeds (from seed {}) x {} steps on {} threads",
+        scenarios.len(), config.seeds, config.base_seed, config.steps, config.threads
+    );
+    let results = run_scenarios(build, scenarios, config);
+    if config.per_run {
+        println!("--- Runs ---");
+        for (scenario, runs) in scenarios.iter().zip(&results) {
+            for r in runs {
+                println!(
+                    "{} {} seed {}: avg wait {:.2}s, max wait {:.1}s, {:.3} kWh, {} served",
+                    scenario.name, scenario.traffic.name(), r.seed, r.average_wait, r.max_wait, r.energy_kwh, r.served
+                );
+            }
+        }
+    }
+    println!("--- Mean over seeds ---");
+    println!("{:<10} {:<8} {:>10} {:>10} {:>9} {:>8}", "dispatch", "traffic", "avg wait s", "max wait s", "kWh", "served");
+    for (scenario, runs) in scenarios.iter().zip(&results) {
+        println!(
+            "{:<10} {:<8} {:>10.2} {:>10.1} {:>9.3} {:>8.1}",
+            scenario.name,
+            scenario.traffic.name(),
+            mean(runs.iter().map(|r| r.average_wait)),
+            mean(runs.iter().map(|r| r.max_wait)),
+            mean(runs.iter().map(|r| r.energy_kwh)),
+            mean(runs.iter().map(|r| r.served as f32)),
+        );
+    }
+}
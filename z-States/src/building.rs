@@ -0,0 +1,287 @@
+// A building is made of one or more elevator banks, each with its own
+// dispatcher and served floors. Traffic is generated here and passengers whose
+// trip no single bank covers are handed over between banks at a shared floor.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::dispatch::Dispatcher;
+use crate::parking::{LobbyZoneParking, NoParking};
+use crate::timing::Timing;
+use crate::{ControlSystem, Elevator, Luggage, Passenger, PassengerState, LOBBY_FLOOR, NUM_FLOORS};
+
+const ARRIVALS_PER_MINUTE: f64 = 8.0;  // Default traffic, one new passenger every 7.5 s on average
+const MAX_PARTY_SIZE: usize = 6;
+
+// Parties of 2 to MAX_PARTY_SIZE people who arrive together and share origin and
+// destination. A party boards only if everyone fits, unless it may split up; a
+// party too large for even an empty car always boards as it fits.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GroupTraffic {
+    pub share: f64,   // Share of arrivals that are a party instead of a single passenger
+    pub split: bool,  // Members board one by one whenever there is room
+}
+
+pub struct Building {
+    pub banks: Vec<ControlSystem>,
+    pub entrances: Vec<i32>,
+    pub entrance_share: f64,  // Share of new passengers who start at an entrance floor
+    pub arrivals_per_minute: f64,
+    pub group_share: f64,  // Share of arrivals that are a party travelling together
+    pub clock_s: f64,  // Simulated time since the start
+    pub next_arrival: Option<u64>,  // Steps before the next passenger appears, drawn when needed
+    pub passenger_counter: usize,
+    pub rng: ChaCha12Rng,  // The generator behind `StdRng`, used directly so snapshots can save its position
+}
+
+impl Building {
+    pub fn new(banks: Vec<ControlSystem>, entrances: Vec<i32>, entrance_share: f64) -> Self {
+        Building {
+            banks,
+            entrances,
+            entrance_share,
+            arrivals_per_minute: ARRIVALS_PER_MINUTE,
+            group_share: 0.0,
+            clock_s: 0.0,
+            next_arrival: None,
+            passenger_counter: 0,
+            rng: ChaCha12Rng::from_entropy(),
+        }
+    }
+
+    // Same seed, same arrivals: needed for reproducible runs and learning environments
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+        self
+    }
+
+    // One bank serving the whole building, arrivals spread evenly over all floors
+    pub fn single(bank: ControlSystem) -> Self {
+        Building::new(vec![bank], vec![LOBBY_FLOOR], 0.0)
+    }
+
+    pub fn set_dispatcher(&mut self, dispatcher: Dispatcher) {
+        for bank in &mut self.banks {
+            bank.dispatcher = dispatcher;
+        }
+    }
+
+    pub fn groups(&self) -> GroupTraffic {
+        GroupTraffic { share: self.group_share, split: self.banks[0].split_groups }
+    }
+
+    pub fn set_groups(&mut self, groups: GroupTraffic) {
+        self.group_share = groups.share;
+        for bank in &mut self.banks {
+            bank.split_groups = groups.split;
+        }
+    }
+
+    pub fn set_parking(&mut self, enabled: bool) {
+        for bank in &mut self.banks {
+            if enabled {
+                bank.set_parking_policy(Box::new(LobbyZoneParking { lobby: LOBBY_FLOOR, num_floors: NUM_FLOORS }));
+            } else {
+                bank.set_parking_policy(Box::new(NoParking));
+            }
+        }
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.banks[0].timing
+    }
+
+    pub fn set_timing(&mut self, timing: Timing) {
+        for bank in &mut self.banks {
+            bank.set_timing(timing);
+        }
+    }
+
+    pub fn clock_seconds(&self) -> f64 {
+        self.clock_s
+    }
+
+    // Average wait of boarded passengers and the longest wait so far, including
+    // passengers who are still waiting, both in seconds
+    pub fn wait_stats(&self) -> (f32, f32) {
+        let boarded: u64 = self.banks.iter().map(|b| b.boardings.total).sum();
+        let average = if boarded == 0 {
+            0.0
+        } else {
+            (self.banks.iter().map(|b| b.waits.wait_sum_s).sum::<f64>() / boarded as f64) as f32
+        };
+        let still_waiting = self.banks.iter().flat_map(|b| b.passengers.iter().map(|p| p.waited_s));
+        let max = self.banks.iter().map(|b| b.waits.max_wait_s).chain(still_waiting).fold(0.0, f32::max);
+        (average, max)
+    }
+
+    // Passengers waiting at each floor, over all banks
+    pub fn waiting_per_floor(&self) -> Vec<usize> {
+        let mut waiting = vec![0; NUM_FLOORS as usize];
+        for passenger in self.banks.iter().flat_map(|b| b.passengers.iter()) {
+            if let PassengerState::Idle(floor) = passenger.state {
+                waiting[floor as usize] += 1;
+            }
+        }
+        waiting
+    }
+
+    // Passengers who boarded or are still waiting after their call was escalated
+    pub fn overdue_passengers(&self) -> usize {
+        let boarded: u64 = self.banks.iter().map(|b| b.waits.overdue).sum();
+        let waiting = self.banks.iter().flat_map(|b| &b.passengers).filter(|p| p.overdue).count();
+        boarded as usize + waiting
+    }
+
+    pub fn total_energy_kwh(&self) -> f32 {
+        self.elevators().map(|e| e.energy.total_kwh()).sum()
+    }
+
+    pub fn elevators(&self) -> impl Iterator<Item = &Elevator> {
+        self.banks.iter().flat_map(|b| b.elevators.iter())
+    }
+
+    pub fn elevator_mut(&mut self, index: usize) -> Option<&mut Elevator> {
+        self.banks.iter_mut().flat_map(|b| b.elevators.iter_mut()).nth(index)
+    }
+
+    // Bank to start in and, if needed, the floor where the passenger changes banks
+    fn route(&self, from: i32, to: i32) -> Option<(usize, Option<i32>)> {
+        if let Some(i) = self.banks.iter().position(|b| b.route(from, to).is_some()) {
+            return Some((i, None));
+        }
+        for (i, first) in self.banks.iter().enumerate() {
+            for (j, second) in self.banks.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                let shared = first.served_floors().intersection(&second.served_floors()).copied().collect::<Vec<_>>();
+                for t in shared {
+                    if first.has_direct_service(from, t) && second.route(t, to).is_some() {
+                        return Some((i, Some(t)));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn add_random_passenger(&mut self) {
+        let rng = &mut self.rng;
+        let floor = if !self.entrances.is_empty() && rng.gen_bool(self.entrance_share) {
+            self.entrances[rng.gen_range(0..self.entrances.len())]
+        } else {
+            rng.gen_range(0..NUM_FLOORS)
+        };
+        let mut destination_floor = rng.gen_range(0..NUM_FLOORS);
+        while floor == destination_floor {
+            destination_floor = rng.gen_range(0..NUM_FLOORS);
+        }
+        // Only drawn with parties switched on, so runs without them keep their arrivals
+        let party_size = if self.group_share > 0.0 && rng.gen_bool(self.group_share) { rng.gen_range(2..=MAX_PARTY_SIZE) } else { 1 };
+        let loads: Vec<(f32, Luggage)> = (0..party_size)
+            .map(|_| {
+                let mass_kg = rng.gen_range(50.0..100.0);
+                let luggage = match rng.gen_range(0..20) {
+                    0 | 1 => Luggage::Suitcase,
+                    2 => Luggage::Trolley,
+                    3 => Luggage::Wheelchair,
+                    _ => Luggage::None,
+                };
+                (mass_kg, luggage)
+            })
+            .collect();
+
+        let Some((bank, transfer_floor)) = self.route(floor, destination_floor) else {
+            sim_log!("No route from floor {} to floor {}, passenger turned away", floor, destination_floor);
+            return;
+        };
+        if party_size > 1 {
+            sim_log!("Party of {} waiting at floor {} for floor {}", party_size, floor, destination_floor);
+        }
+        let group = (party_size > 1).then_some(self.passenger_counter);
+        for (mass_kg, luggage) in loads {
+            let mut passenger = Passenger::new(
+                self.passenger_counter,
+                floor,
+                destination_floor,
+            ).with_load(mass_kg, luggage);
+            passenger.arrived_s = self.clock_s;
+            passenger.group = group;
+            self.banks[bank].add_passenger(match transfer_floor {
+                Some(transfer_floor) => passenger.via(transfer_floor),
+                None => passenger,
+            });
+            self.passenger_counter += 1;
+        }
+    }
+
+    // Steps to go before the next passenger appears. Each step has the same chance
+    // of an arrival, so the gap is drawn at once from the geometric distribution;
+    // a new traffic level applies from the next gap on. Parties arrive less often,
+    // so the number of people per minute stays the same.
+    pub fn steps_to_arrival(&mut self) -> u64 {
+        if let Some(steps) = self.next_arrival {
+            return steps;
+        }
+        let tick_seconds = self.timing().tick_seconds;
+        let mean_party_size = 1.0 + self.group_share * ((2 + MAX_PARTY_SIZE) as f64 / 2.0 - 1.0);
+        let arrival_probability = (self.arrivals_per_minute / mean_party_size / 60.0 * tick_seconds as f64).min(1.0);
+        let steps = if arrival_probability >= 1.0 {
+            0
+        } else if arrival_probability <= 0.0 {
+            u64::MAX
+        } else {
+            let u: f64 = self.rng.gen();
+            ((1.0 - u).ln() / (1.0 - arrival_probability).ln()).floor() as u64
+        };
+        self.next_arrival = Some(steps);
+        steps
+    }
+
+    // No more passengers from now on, e.g. to let the building empty
+    pub fn stop_arrivals(&mut self) {
+        self.arrivals_per_minute = 0.0;
+        self.next_arrival = None;
+    }
+
+    // Moves on `steps` steps in which no passenger arrives and no car changes state
+    pub fn idle(&mut self, steps: u64) {
+        self.next_arrival = Some(self.steps_to_arrival() - steps);
+        self.clock_s += self.timing().tick_seconds as f64 * steps as f64;
+        for bank in &mut self.banks {
+            bank.idle(steps);
+        }
+    }
+
+    pub fn step(&mut self) {
+        let tick_seconds = self.timing().tick_seconds;
+        match self.steps_to_arrival() {
+            0 => {
+                self.next_arrival = None;
+                self.add_random_passenger();
+            }
+            steps => self.next_arrival = Some(steps - 1),
+        }
+        self.clock_s += tick_seconds as f64;
+
+        for bank in &mut self.banks {
+            bank.step();
+        }
+
+        // Move passengers who finished their first leg over to the bank that serves the rest
+        let handoffs: Vec<Passenger> = self.banks.iter_mut().flat_map(|b| b.handoffs.drain(..)).collect();
+        for passenger in handoffs {
+            let PassengerState::Idle(floor) = passenger.state else {
+                continue;
+            };
+            match self.banks.iter().position(|b| b.route(floor, passenger.destination).is_some()) {
+                Some(bank) => {
+                    sim_log!("Passenger {} changing banks at floor {}", passenger.id, floor);
+                    self.banks[bank].add_passenger(passenger);
+                }
+                None => sim_log!("Passenger {} stranded at floor {}", passenger.id, floor),
+            }
+        }
+    }
+}
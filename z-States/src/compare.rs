@@ -0,0 +1,195 @@
+// Paired comparison of two configurations. Both run on the same seeds, so each
+// seed gives one difference per metric; the report shows the mean difference
+// with a 95% confidence interval and the p-value of a paired t-test on it.
+//
+// A configuration is written as a dispatcher, `nearest`, `eta` or `eta=WAIT,RIDE`,
+// optionally followed by `+parking` or `+no-parking`, e.g. `eta=1,0.5+no-parking`.
+
+use crate::batch::{self, BatchConfig, RunResult, Scenario, TrafficProfile};
+use crate::building::Building;
+use crate::dispatch::{Dispatcher, EtaWeights};
+
+pub fn parse_config(spec: &str, traffic: TrafficProfile) -> Result<Scenario, String> {
+    let mut parts = spec.split('+');
+    let dispatcher = match parts.next().unwrap_or("") {
+        "nearest" => Dispatcher::Nearest,
+        "eta" => Dispatcher::Eta(EtaWeights::default()),
+        other => {
+            let Some(text) = other.strip_prefix("eta=") else {
+                return Err(format!("unknown dispatcher '{}'", other));
+            };
+            let values: Vec<f32> = text.split(',').filter_map(|v| v.trim().parse().ok()).collect();
+            let [wait, ride_delay] = values[..] else {
+                return Err(format!("malformed ETA weights '{}', expected eta=WAIT,RIDE", text));
+            };
+            Dispatcher::Eta(EtaWeights { wait, ride_delay })
+        }
+    };
+    let mut parking = None;
+    for option in parts {
+        parking = match option {
+            "parking" => Some(true),
+            "no-parking" => Some(false),
+            _ => return Err(format!("unknown option '{}'", option)),
+        };
+    }
+    Ok(Scenario { name: spec.to_string(), dispatcher, traffic, parking })
+}
+
+pub struct PairedSummary {
+    pub mean_a: f64,
+    pub mean_b: f64,
+    pub difference: f64,  // Mean of b - a
+    pub ci_low: f64,
+    pub ci_high: f64,
+    pub p_value: f64,
+}
+
+pub fn paired_summary(a: &[f64], b: &[f64]) -> PairedSummary {
+    let n = a.len().min(b.len());
+    let differences: Vec<f64> = a.iter().zip(b).map(|(x, y)| y - x).collect();
+    let mean = |values: &[f64]| values.iter().fold(0.0, |sum, v| sum + v) / values.len().max(1) as f64;
+    let difference = mean(&differences);
+
+    let (ci_low, ci_high, p_value) = if n < 2 {
+        (f64::NEG_INFINITY, f64::INFINITY, 1.0)
+    } else {
+        let df = (n - 1) as f64;
+        let variance = differences.iter().fold(0.0, |sum, d| sum + (d - difference).powi(2)) / df;
+        let standard_error = (variance / n as f64).sqrt();
+        if standard_error == 0.0 {
+            // Every seed gave the same difference
+            (difference, difference, if difference == 0.0 { 1.0 } else { 0.0 })
+        } else {
+            let margin = t_quantile_975(df) * standard_error;
+            let t = difference / standard_error;
+            (difference - margin, difference + margin, t_two_sided_p(t, df))
+        }
+    };
+    PairedSummary { mean_a: mean(&a[..n]), mean_b: mean(&b[..n]), difference, ci_low, ci_high, p_value }
+}
+
+// P(|T| >= |t|) for Student's t with `df` degrees of freedom
+fn t_two_sided_p(t: f64, df: f64) -> f64 {
+    incomplete_beta(df / 2.0, 0.5, df / (df + t * t))
+}
+
+// Critical value for a two-sided 95% interval, found by bisection on the p-value
+fn t_quantile_975(df: f64) -> f64 {
+    let (mut low, mut high) = (0.0, 1000.0);
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if t_two_sided_p(mid, df) > 0.05 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+fn ln_gamma(x: f64) -> f64 {
+    // Lanczos approximation, g = 7
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| sum + c / (x + i as f64 + 1.0));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+// Regularized incomplete beta function I_x(a, b)
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The continued fraction converges quickly only below the mean
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+// Lentz's method for the incomplete beta continued fraction
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut result = d;
+    for m in 1..300 {
+        let m = m as f64;
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            result *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+    result
+}
+
+type Metric = fn(&RunResult) -> f64;
+
+pub fn run(build: fn() -> Building, a: &Scenario, b: &Scenario, config: &BatchConfig) {
+    println!(
+        "Comparing A = {} and B = {} under {} traffic, {} paired seeds (from seed {}) x {} steps",
+        a.name, b.name, a.traffic.name(), config.seeds, config.base_seed, config.steps
+    );
+    let results = batch::run_scenarios(build, &[a.clone(), b.clone()], config);
+    let metrics: [(&str, Metric); 4] = [
+        ("avg wait s", |r| r.average_wait as f64),
+        ("max wait s", |r| r.max_wait as f64),
+        ("kWh", |r| r.energy_kwh as f64),
+        ("served", |r| r.served as f64),
+    ];
+    println!(
+        "{:<10} {:>10} {:>10} {:>10} {:>23} {:>8}",
+        "metric", "A", "B", "B - A", "95% CI", "p"
+    );
+    for (name, value) in metrics {
+        let a_values: Vec<f64> = results[0].iter().map(value).collect();
+        let b_values: Vec<f64> = results[1].iter().map(value).collect();
+        let s = paired_summary(&a_values, &b_values);
+        let verdict = if s.p_value < 0.05 { "significant" } else { "" };
+        println!(
+            "{:<10} {:>10.3} {:>10.3} {:>+10.3} [{:>+10.3}, {:>+10.3}] {:>8.4} {}",
+            name, s.mean_a, s.mean_b, s.difference, s.ci_low, s.ci_high, s.p_value, verdict
+        );
+    }
+}
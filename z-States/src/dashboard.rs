@@ -0,0 +1,120 @@
+// Statistics panel drawn next to the building in the viewer: running wait
+// figures, queue bars per floor, car utilisation and a rolling wait chart.
+
+use std::collections::VecDeque;
+
+use ggez::glam::Vec2;
+use ggez::graphics::{self, Canvas, Color, Text};
+use ggez::{Context, GameResult};
+
+use crate::building::Building;
+use crate::tracker::StepTracker;
+use crate::NUM_FLOORS;
+
+const CHART_TICKS: usize = 120;  // Ticks shown in the wait chart
+const CHART_WINDOW: usize = 20;  // Ticks of boardings averaged for each chart point
+const PANEL_WIDTH: f32 = 280.0;
+
+#[derive(Default)]
+pub struct Dashboard {
+    tracker: StepTracker,
+    recent_boardings: VecDeque<Vec<f32>>,  // Waits of passengers who boarded, per tick
+    chart: VecDeque<f32>,                  // Rolling average wait per tick
+}
+
+impl Dashboard {
+    pub fn update(&mut self, building: &Building) {
+        let boarded = self.tracker.update(building).iter().map(|b| b.wait_s).collect();
+        self.recent_boardings.push_back(boarded);
+        if self.recent_boardings.len() > CHART_WINDOW {
+            self.recent_boardings.pop_front();
+        }
+
+        // Keep the last point when nobody boarded so the line does not drop to zero
+        let (sum, count) = self
+            .recent_boardings
+            .iter()
+            .flatten()
+            .fold((0.0, 0), |(sum, count), &w| (sum + w, count + 1));
+        let point = if count > 0 { sum / count as f32 } else { self.chart.back().copied().unwrap_or(0.0) };
+        self.chart.push_back(point);
+        if self.chart.len() > CHART_TICKS {
+            self.chart.pop_front();
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context, canvas: &mut Canvas, building: &Building, left: f32) -> GameResult {
+        let draw_param = graphics::DrawParam::default();
+        let text = |canvas: &mut Canvas, content: String, x: f32, y: f32| {
+            canvas.draw(&Text::new(content), graphics::DrawParam::default().dest(Vec2::new(x, y)).color(Color::BLACK));
+        };
+        canvas.draw(
+            &graphics::Mesh::new_line(ctx, &[Vec2::new(left - 10.0, 0.0), Vec2::new(left - 10.0, 600.0)], 1.0, Color::BLACK)?,
+            draw_param,
+        );
+
+        let (average_wait, max_wait) = building.wait_stats();
+        let served: usize = building.elevators().map(|e| e.passengers_served).sum();
+        let clock = building.clock_seconds() as u64;
+        text(canvas, format!("Statistics at {}:{:02}:{:02}", clock / 3600, clock / 60 % 60, clock % 60), left, 10.0);
+        text(canvas, format!("Avg wait {:.1}s", average_wait), left, 35.0);
+        text(canvas, format!("Max wait {:.1}s", max_wait), left, 55.0);
+        text(canvas, format!("Served {}", served), left, 75.0);
+
+        // Queue per floor, top floor first like the building
+        let waiting = building.waiting_per_floor();
+        text(canvas, "Queue per floor".to_string(), left, 105.0);
+        for (row, floor) in (0..NUM_FLOORS as usize).rev().enumerate() {
+            let y = 128.0 + row as f32 * 20.0;
+            text(canvas, format!("F{} {:>2}", floor, waiting[floor]), left, y);
+            if waiting[floor] > 0 {
+                let width = (waiting[floor] as f32 * 10.0).min(PANEL_WIDTH - 70.0);
+                canvas.draw(
+                    &graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), graphics::Rect::new(left + 60.0, y + 2.0, width, 12.0), Color::RED)?,
+                    draw_param,
+                );
+            }
+        }
+
+        // Share of steps each car was busy
+        let utilisation_top = 138.0 + NUM_FLOORS as f32 * 20.0;
+        text(canvas, "Car utilisation".to_string(), left, utilisation_top);
+        for (car, share) in self.tracker.utilisation().into_iter().enumerate() {
+            let y = utilisation_top + 23.0 + car as f32 * 20.0;
+            let share = share as f32;
+            text(canvas, format!("E{} {:>3.0}%", car, share * 100.0), left, y);
+            if share > 0.0 {
+                canvas.draw(
+                    &graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), graphics::Rect::new(left + 70.0, y + 2.0, share * (PANEL_WIDTH - 80.0), 12.0), Color::BLUE)?,
+                    draw_param,
+                );
+            }
+        }
+
+        // Rolling average wait, scaled to the highest point shown
+        let chart_height = 100.0;
+        let chart_bottom = 580.0;
+        let peak = self.chart.iter().copied().fold(1.0, f32::max);
+        text(canvas, format!("Avg wait, last {} ticks (max {:.1}s)", CHART_TICKS, peak), left, chart_bottom - chart_height - 22.0);
+        canvas.draw(
+            &graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::stroke(1.0),
+                graphics::Rect::new(left, chart_bottom - chart_height, PANEL_WIDTH - 10.0, chart_height),
+                Color::BLACK,
+            )?,
+            draw_param,
+        );
+        if self.chart.len() > 1 {
+            let step = (PANEL_WIDTH - 10.0) / (CHART_TICKS - 1) as f32;
+            let points: Vec<Vec2> = self
+                .chart
+                .iter()
+                .enumerate()
+                .map(|(i, &w)| Vec2::new(left + i as f32 * step, chart_bottom - w / peak * chart_height))
+                .collect();
+            canvas.draw(&graphics::Mesh::new_line(ctx, &points, 2.0, Color::RED)?, draw_param);
+        }
+        Ok(())
+    }
+}
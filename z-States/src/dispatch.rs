@@ -0,0 +1,125 @@
+// Cost functions the control system uses to pick a car for a hall call. The
+// lowest cost wins; `Nearest` is the original distance and "on the way" rule,
+// except that a car which already has the stop queued costs its distance,
+// `Eta` tries the call on a copy of each car's plan and weighs the pickup time
+// against the delay it causes to passengers already riding. With `External` the
+// control system leaves hall calls alone and waits for `assign_call`.
+// A call stays with the car it was given to until another car costs less than
+// `REASSIGN_RATIO` times as much.
+
+use std::collections::VecDeque;
+
+use crate::{CabinState, DoorState, Elevator};
+
+pub const REASSIGN_RATIO: f32 = 0.7;
+
+#[derive(Debug, Clone, Copy)]
+pub struct EtaWeights {
+    pub wait: f32,        // Per second until the new passenger is picked up
+    pub ride_delay: f32,  // Per second of extra ride time for passengers on board
+}
+
+impl Default for EtaWeights {
+    fn default() -> Self {
+        EtaWeights { wait: 1.0, ride_delay: 1.0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Dispatcher {
+    Nearest,
+    Eta(EtaWeights),
+    External,  // Calls are assigned from outside, e.g. by a learning agent
+}
+
+impl Dispatcher {
+    pub fn cost(&self, elevator: &Elevator, pickup_stop: i32, destination: i32) -> f32 {
+        match self {
+            // A car already on its way to the stop is as far as it is, whichever way it moves
+            Dispatcher::Nearest if elevator.destinations.contains(&pickup_stop) => (elevator.position() - pickup_stop).abs() as f32,
+            Dispatcher::Nearest => nearest_cost(elevator, pickup_stop, destination) as f32,
+            Dispatcher::Eta(weights) => eta_cost(elevator, pickup_stop, weights),
+            Dispatcher::External => 0.0,
+        }
+    }
+}
+
+fn nearest_cost(elevator: &Elevator, floor: i32, destination: i32) -> i32 {
+    match elevator.cabin_state {
+        CabinState::Standing(e_floor) | CabinState::Holding(e_floor) => {
+            (e_floor - floor).abs()
+        },
+        CabinState::Moving(from, to) => {
+            // Check if passenger is "on the way"
+            let elevator_going_up = to > from;
+            let passenger_going_up = destination > floor;
+
+            if elevator_going_up == passenger_going_up {  // Same direction
+                if elevator_going_up {
+                    // Going up: passenger should be between current position and destination
+                    if floor >= from && floor <= to {
+                        0  // Perfect match!
+                    } else {
+                        i32::MAX  // Wrong direction
+                    }
+                } else {
+                    // Going down: passenger should be between destination and current position
+                    if floor <= from && floor >= to {
+                        0  // Perfect match!
+                    } else {
+                        i32::MAX  // Wrong direction
+                    }
+                }
+            } else {
+                i32::MAX  // Wrong direction
+            }
+        }
+    }
+}
+
+fn eta_cost(elevator: &Elevator, pickup_stop: i32, weights: &EtaWeights) -> f32 {
+    // The car's stops with the pickup added, as `add_destination` would order them
+    let plan = if elevator.destinations.contains(&pickup_stop) {
+        elevator.destinations.clone()
+    } else {
+        elevator.sorted_stops(elevator.destinations.iter().copied().chain([pickup_stop]).collect())
+    };
+
+    let wait = arrival_time(elevator, &plan, pickup_stop).unwrap_or(f32::MAX);
+    let ride_delay = onboard_arrival_total(elevator, &plan) - onboard_arrival_total(elevator, &elevator.destinations);
+    weights.wait * wait + weights.ride_delay * ride_delay.max(0.0)
+}
+
+// Seconds until the car reaches each of `stops`, served in order
+fn arrival_times(elevator: &Elevator, stops: &VecDeque<i32>) -> Vec<(i32, f32)> {
+    let timing = &elevator.timing;
+    let mut time = match (&elevator.cabin_state, &elevator.door_state) {
+        (CabinState::Holding(_), DoorState::Closed) => 0.0,
+        (CabinState::Holding(_), _) => timing.seconds_per_stop() / 2.0,  // Finish the current stop first
+        _ => 0.0,
+    };
+    let mut position = elevator.position();
+    let mut times = Vec::new();
+    for &stop in stops {
+        time += (stop - position).abs() as f32 * timing.seconds_per_floor();
+        times.push((stop, time));
+        time += timing.seconds_per_stop();
+        position = stop;
+    }
+    times
+}
+
+fn arrival_time(elevator: &Elevator, stops: &VecDeque<i32>, stop: i32) -> Option<f32> {
+    if elevator.position() == stop && matches!(elevator.cabin_state, CabinState::Holding(_)) {
+        return Some(0.0);
+    }
+    arrival_times(elevator, stops).into_iter().find(|&(s, _)| s == stop).map(|(_, t)| t)
+}
+
+fn onboard_arrival_total(elevator: &Elevator, stops: &VecDeque<i32>) -> f32 {
+    let times = arrival_times(elevator, stops);
+    elevator.passengers
+        .iter()
+        .filter_map(|p| times.iter().find(|&&(s, _)| s == p.cabin_stop()).map(|&(_, t)| t))
+        .sum()
+}
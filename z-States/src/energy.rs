@@ -0,0 +1,91 @@
+// Energy bookkeeping for a single car. Travel energy depends on the imbalance
+// between cabin plus load and the counterweight, so a light car going up (or a
+// full car going down) is driven by the counterweight and can feed energy back.
+
+const GRAVITY: f32 = 9.81;
+const JOULES_PER_KWH: f32 = 3_600_000.0;
+
+#[derive(Debug, Clone)]
+pub struct EnergyModel {
+    pub car_mass_kg: f32,
+    pub counterweight_kg: f32,
+    pub floor_height_m: f32,
+    pub motor_efficiency: f32,
+    pub regen_efficiency: f32,  // 0.0 disables regeneration
+    pub friction_j_per_floor: f32,
+    pub door_operation_j: f32,
+    pub standby_w: f32,
+}
+
+impl EnergyModel {
+    pub fn new(rated_load_kg: f32) -> Self {
+        let car_mass_kg = 500.0;
+        EnergyModel {
+            car_mass_kg,
+            counterweight_kg: car_mass_kg + 0.5 * rated_load_kg,  // Usual 50% balance
+            floor_height_m: 3.0,
+            motor_efficiency: 0.8,
+            regen_efficiency: 0.6,
+            friction_j_per_floor: 2_000.0,
+            door_operation_j: 300.0,
+            standby_w: 150.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EnergyMeter {
+    pub model: EnergyModel,
+    pub travel_j: f32,
+    pub door_j: f32,
+    pub standby_j: f32,
+    pub regenerated_j: f32,
+}
+
+impl EnergyMeter {
+    pub fn new(model: EnergyModel) -> Self {
+        EnergyMeter {
+            model,
+            travel_j: 0.0,
+            door_j: 0.0,
+            standby_j: 0.0,
+            regenerated_j: 0.0,
+        }
+    }
+
+    pub fn record_travel(&mut self, load_kg: f32, going_up: bool) {
+        let imbalance_kg = self.model.car_mass_kg + load_kg - self.model.counterweight_kg;
+        let mut work_j = imbalance_kg * GRAVITY * self.model.floor_height_m;
+        if !going_up {
+            work_j = -work_j;
+        }
+
+        self.travel_j += self.model.friction_j_per_floor / self.model.motor_efficiency;
+        if work_j > 0.0 {
+            self.travel_j += work_j / self.model.motor_efficiency;
+        } else {
+            self.regenerated_j += -work_j * self.model.regen_efficiency;
+        }
+    }
+
+    pub fn record_door_operation(&mut self) {
+        self.door_j += self.model.door_operation_j;
+    }
+
+    pub fn record_standby(&mut self, seconds: f32) {
+        self.standby_j += self.model.standby_w * seconds;
+    }
+
+    // Drawn from the supply, only ever grows
+    pub fn consumed_kwh(&self) -> f32 {
+        (self.travel_j + self.door_j + self.standby_j) / JOULES_PER_KWH
+    }
+
+    pub fn regenerated_kwh(&self) -> f32 {
+        self.regenerated_j / JOULES_PER_KWH
+    }
+
+    pub fn total_kwh(&self) -> f32 {
+        (self.travel_j + self.door_j + self.standby_j - self.regenerated_j) / JOULES_PER_KWH
+    }
+}
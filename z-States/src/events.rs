@@ -0,0 +1,218 @@
+// Discrete-event engine: instead of running every step, look ahead to the next
+// step in which something happens (a passenger arrives, a car reaches a floor,
+// a door finishes moving, the doors are done with boarding, a car departs or
+// parks, a hall call is handed out or waits past the limit) and jump straight there. The steps in
+// between only count time, so the run is the same as in tick mode while
+// long or quiet stretches cost next to nothing.
+
+use std::collections::BTreeMap;
+
+use crate::building::Building;
+use crate::dispatch::Dispatcher;
+use crate::{CabinState, ControlSystem, DoorState, PassengerState, ServiceMode, PHASE_EPSILON};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Arrival,
+    Dispatch,
+    CarArrives(usize),
+    DoorFinished(usize),
+    BoardingComplete(usize),
+    Departure(usize),
+    Parking(usize),
+    Escalation,
+}
+
+impl Event {
+    fn name(&self) -> &'static str {
+        match self {
+            Event::Arrival => "arrival",
+            Event::Dispatch => "dispatch",
+            Event::CarArrives(_) => "car arrives",
+            Event::DoorFinished(_) => "door finished",
+            Event::BoardingComplete(_) => "boarding complete",
+            Event::Departure(_) => "departure",
+            Event::Parking(_) => "parking",
+            Event::Escalation => "escalation",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EventStats {
+    pub steps: u64,
+    pub skipped: u64,  // Steps jumped over without running them
+    counts: BTreeMap<&'static str, u64>,
+}
+
+impl EventStats {
+    pub fn events(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    pub fn report(&self) {
+        let share = if self.steps > 0 { self.skipped as f64 / self.steps as f64 * 100.0 } else { 0.0 };
+        println!("Event engine: {} events over {} steps, {} steps skipped ({:.1}%)", self.events(), self.steps, self.skipped, share);
+        let counts: Vec<String> = self.counts.iter().map(|(name, count)| format!("{} {}", name, count)).collect();
+        println!("  {}", counts.join(", "));
+    }
+}
+
+// Steps until the one in which `elapsed` reaches `duration`; every step before
+// that only adds time, just like the checks in `Elevator::step`
+fn steps_until(elapsed: f32, duration: f32, tick_seconds: f32) -> u64 {
+    let steps = ((duration - PHASE_EPSILON - elapsed) / tick_seconds).ceil();
+    (steps.max(1.0) as u64) - 1
+}
+
+// Next thing car `i` does on its own, or None while it waits for outside events
+fn car_event(bank: &ControlSystem, i: usize, car: usize) -> Option<(u64, Event)> {
+    let elevator = &bank.elevators[i];
+    let tick_seconds = bank.timing.tick_seconds;
+    let phase_end = steps_until(
+        elevator.phase_s,
+        elevator.timing.phase_seconds(&elevator.cabin_state, &elevator.door_state, elevator.moved_this_stop),
+        tick_seconds,
+    );
+    // Cars sharing a shaft may block each other, so they run every step while busy
+    let phase_end = if bank.shaft_of(i).is_some() && !elevator.is_idle() { 0 } else { phase_end };
+
+    match (&elevator.cabin_state, &elevator.door_state) {
+        (CabinState::Moving(_, _), _) => Some((phase_end, Event::CarArrives(car))),
+        (CabinState::Holding(floor), DoorState::Open) => {
+            let exiting = elevator.passengers.iter().any(|p| p.state == PassengerState::InCabin && p.cabin_stop() == *floor);
+            let boarding_floors = elevator.boarding_floors(*floor);
+            let boarding = !elevator.is_full()
+                && bank.passengers.iter().any(|p| match p.state {
+                    PassengerState::Idle(f) => boarding_floors.iter().any(|&(_, b)| b == f) && elevator.can_carry(f, p.destination),
+                    _ => false,
+                });
+            if exiting || boarding {
+                Some((0, Event::BoardingComplete(car)))
+            } else if elevator.service_mode == ServiceMode::Group || elevator.has_car_call(*floor) {
+                Some((phase_end, Event::BoardingComplete(car)))
+            } else {
+                None  // Held open until a car call
+            }
+        }
+        (CabinState::Holding(_), _) => Some((phase_end, Event::DoorFinished(car))),
+        (CabinState::Standing(_), DoorState::Closed) if !elevator.destinations.is_empty() => Some((phase_end, Event::Departure(car))),
+        (CabinState::Standing(_), DoorState::Closed) => {
+            (elevator.is_idle() && elevator.service_mode == ServiceMode::Group)
+                .then(|| (steps_until(elevator.idle_s, bank.timing.idle_timeout_s, tick_seconds), Event::Parking(car)))
+        }
+        (CabinState::Standing(_), _) => Some((0, Event::Departure(car))),
+    }
+}
+
+// True if the dispatcher would hand out a hall call it has not handed out yet
+fn dispatch_pending(bank: &ControlSystem) -> bool {
+    if matches!(bank.dispatcher, Dispatcher::External) {
+        return false;
+    }
+    // With nobody waiting there is nothing to hand out, but calls of passengers who have boarded are still withdrawn
+    let waiting = bank.passengers.iter().any(|p| matches!(p.state, PassengerState::Idle(_)));
+    if !waiting && bank.elevators.iter().all(|e| e.assigned_calls.is_empty()) {
+        return false;
+    }
+    let mut probe = bank.elevators.clone();
+    bank.assign_hall_calls(&mut probe);
+    probe.iter().zip(&bank.elevators).any(|(after, before)| {
+        after.destinations != before.destinations || after.priority_stop != before.priority_stop || after.assigned_calls != before.assigned_calls
+    })
+}
+
+// Steps before the first step that starts with a waiting passenger past the
+// limit, or past the longer limit after which cars with riders are asked too
+fn escalation_event(bank: &ControlSystem) -> Option<(u64, Event)> {
+    let busy_escalation_s = bank.busy_escalation_s();
+    let steps = bank
+        .passengers
+        .iter()
+        .filter(|p| matches!(p.state, PassengerState::Idle(_)))
+        .filter_map(|p| match p.overdue {
+            false => Some(bank.timing.max_wait_s),
+            true => (p.waited_s + PHASE_EPSILON < busy_escalation_s).then_some(busy_escalation_s),
+        }.map(|limit| ((limit - PHASE_EPSILON - p.waited_s) / bank.timing.tick_seconds).ceil().max(0.0) as u64))
+        .min()?;
+    Some((steps, Event::Escalation))
+}
+
+// Steps to wait before the next event, and which event it is
+pub fn next_event(building: &mut Building) -> (u64, Event) {
+    let mut next = (building.steps_to_arrival(), Event::Arrival);
+    let mut car = 0;
+    for bank in &building.banks {
+        if let Some(event) = escalation_event(bank) {
+            if event.0 < next.0 {
+                next = event;
+            }
+        }
+        for i in 0..bank.elevators.len() {
+            if let Some(event) = car_event(bank, i, car) {
+                if event.0 < next.0 {
+                    next = event;
+                }
+            }
+            car += 1;
+        }
+    }
+    // Trying the dispatcher is the costly part, only worth it if there are steps to skip
+    if next.0 > 0 && building.banks.iter().any(dispatch_pending) {
+        return (0, Event::Dispatch);
+    }
+    next
+}
+
+// Jumps over the quiet steps before the next event and runs the step it happens in,
+// at most `max_steps` in all; returns the number of steps taken
+pub fn advance(building: &mut Building, max_steps: u64, stats: &mut EventStats) -> u64 {
+    if max_steps == 0 {
+        return 0;
+    }
+    let (wait, event) = next_event(building);
+    let skip = wait.min(max_steps);
+    if skip > 0 {
+        building.idle(skip);
+        stats.skipped += skip;
+    }
+    stats.steps += skip;
+    if skip == max_steps {
+        return skip;
+    }
+    building.step();
+    stats.steps += 1;
+    *stats.counts.entry(event.name()).or_insert(0) += 1;
+    skip + 1
+}
+
+pub fn run(building: &mut Building, steps: u64) -> EventStats {
+    let mut stats = EventStats::default();
+    let mut done = 0;
+    while done < steps {
+        done += advance(building, steps - done, &mut stats);
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::harness::Scenario;
+    use crate::snapshot;
+
+    #[test]
+    fn events_match_tick_mode() {
+        crate::QUIET.store(true, std::sync::atomic::Ordering::Relaxed);
+        for seed in 0..40 {
+            let mut scenario = Scenario::random(seed);
+            scenario.steps = scenario.steps.min(800);
+            let mut ticked = scenario.building();
+            for _ in 0..scenario.steps {
+                ticked.step();
+            }
+            let mut jumped = scenario.building();
+            super::run(&mut jumped, scenario.steps as u64);
+            assert!(snapshot::text(&ticked) == snapshot::text(&jumped), "event mode differs from tick mode: {}", scenario);
+        }
+    }
+}
@@ -0,0 +1,372 @@
+// Property-based checking: random buildings, dispatchers, timings and traffic
+// run with the invariant checker after every step, then with arrivals stopped
+// until everyone is delivered. A failing scenario is shrunk step by step towards
+// the plainest one that still fails. That one is printed as a description only;
+// `--check 1 --seed=N` reruns the original scenario N.
+
+use std::fmt;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::batch;
+use crate::building::{Building, GroupTraffic};
+use crate::dispatch::{Dispatcher, EtaWeights};
+use crate::invariants;
+use crate::timing::Timing;
+use crate::ServiceMode;
+
+type Preset = (&'static str, fn() -> Building);
+
+const PRESETS: [Preset; 5] = [
+    ("default", crate::default_building),
+    ("--zoned", crate::sky_lobby_building),
+    ("--banks", crate::two_bank_building),
+    ("--double-deck", crate::double_deck_building),
+    ("--twin", crate::twin_building),
+];
+const DRAIN_SECONDS: f32 = 3600.0;  // Everyone left must be delivered this long after arrivals stop
+
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub seed: u64,
+    pub preset: usize,  // Index into PRESETS
+    pub dispatcher: Dispatcher,
+    pub parking: bool,
+    pub timing: Timing,
+    pub arrivals_per_minute: f64,
+    pub groups: GroupTraffic,
+    pub steps: usize,  // Steps with traffic, before the building is left to empty
+    pub attendant: bool,  // A car the rest of its bank can stand in for runs attendant service
+}
+
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub step: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Scenario {
+    // Seed and building first, then only what differs from a default run
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "seed {}, {} building, {} steps, {} arrivals per minute", self.seed, PRESETS[self.preset].0, self.steps, self.arrivals_per_minute)?;
+        if let Dispatcher::Eta(w) = self.dispatcher {
+            write!(f, ", --eta={},{}", w.wait, w.ride_delay)?;
+        }
+        if !self.parking {
+            write!(f, ", parking off")?;
+        }
+        if self.groups.share > 0.0 {
+            write!(f, ", --groups={}", self.groups.share)?;
+        }
+        if self.groups.split {
+            write!(f, ", --split-groups")?;
+        }
+        if self.attendant {
+            write!(f, ", attendant car")?;
+        }
+        let (t, d) = (&self.timing, Timing::default());
+        let flags = [
+            ("--tick", t.tick_seconds, d.tick_seconds),
+            ("--travel", t.travel_per_floor_s, d.travel_per_floor_s),
+            ("--settle", t.settle_s, d.settle_s),
+            ("--door-opening", t.door_opening_s, d.door_opening_s),
+            ("--door-open", t.door_open_s, d.door_open_s),
+            ("--door-closing", t.door_closing_s, d.door_closing_s),
+            ("--boarding", t.boarding_s, d.boarding_s),
+            ("--idle-timeout", t.idle_timeout_s, d.idle_timeout_s),
+            ("--max-wait", t.max_wait_s, d.max_wait_s),
+        ];
+        for (flag, value, default) in flags {
+            if value != default {
+                write!(f, ", {}={}", flag, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Scenario {
+    pub fn random(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut pick = |choices: &[f32]| *choices.choose(&mut rng).unwrap();
+        let timing = Timing {
+            tick_seconds: pick(&[0.25, 0.5, 1.0, 1.5]),
+            travel_per_floor_s: pick(&[0.5, 1.0, 1.5, 3.0]),
+            settle_s: pick(&[0.5, 1.0, 1.5]),
+            door_opening_s: pick(&[0.5, 1.5, 2.5]),
+            door_open_s: pick(&[0.5, 1.5, 3.0]),
+            door_closing_s: pick(&[0.5, 1.5, 2.5]),
+            boarding_s: pick(&[0.0, 0.5, 1.0]),
+            idle_timeout_s: pick(&[1.5, 7.5, 30.0]),
+            max_wait_s: pick(&[15.0, 60.0, 120.0, 600.0]),
+        };
+        let dispatcher = if rng.gen_bool(0.5) {
+            Dispatcher::Nearest
+        } else {
+            // Rounded so the weights print exactly as flags
+            Dispatcher::Eta(EtaWeights { wait: rng.gen_range(1..30) as f32 / 10.0, ride_delay: rng.gen_range(0..30) as f32 / 10.0 })
+        };
+        Scenario {
+            seed,
+            preset: rng.gen_range(0..PRESETS.len()),
+            dispatcher,
+            parking: rng.gen_bool(0.7),
+            timing,
+            arrivals_per_minute: *[2.0, 4.0, 8.0, 12.0].choose(&mut rng).unwrap(),
+            groups: GroupTraffic { share: *[0.0, 0.2, 0.5].choose(&mut rng).unwrap(), split: rng.gen_bool(0.5) },
+            steps: rng.gen_range(200..=2000),
+            attendant: rng.gen_bool(0.3),
+        }
+    }
+
+    pub fn building(&self) -> Building {
+        let mut building = (PRESETS[self.preset].1)().with_seed(self.seed);
+        building.set_timing(self.timing);
+        building.set_dispatcher(self.dispatcher);
+        building.set_parking(self.parking);
+        building.arrivals_per_minute = self.arrivals_per_minute;
+        building.set_groups(self.groups);
+        if self.attendant {
+            // An empty attendant car answers no hall calls, so only a car outside a
+            // shared shaft whose every trip another car makes can run one
+            let bank = &mut building.banks[0];
+            let covered = |i: usize| {
+                let car = &bank.elevators[i];
+                bank.shaft_of(i).is_none()
+                    && car.served_floors.iter().all(|&from| {
+                        car.served_floors.iter().all(|&to| {
+                            from == to || bank.elevators.iter().enumerate().any(|(j, other)| j != i && other.can_carry(from, to))
+                        })
+                    })
+            };
+            if let Some(i) = (0..bank.elevators.len()).find(|&i| covered(i)) {
+                bank.elevators[i].set_service_mode(ServiceMode::Attendant);
+            }
+        }
+        building
+    }
+
+    // Variants that are one change closer to a plain default run, most promising first
+    fn simpler(&self, failure: &Failure) -> Vec<Scenario> {
+        let mut candidates = Vec::new();
+        let mut variant = |change: &dyn Fn(&mut Scenario)| {
+            let mut candidate = self.clone();
+            change(&mut candidate);
+            candidates.push(candidate);
+        };
+        if failure.step < self.steps {
+            variant(&|s| s.steps = failure.step);
+        }
+        if self.steps > 1 {
+            variant(&|s| s.steps /= 2);
+            variant(&|s| s.steps -= s.steps.div_ceil(10));
+        }
+        if self.preset != 0 {
+            variant(&|s| s.preset = 0);
+        }
+        if !matches!(self.dispatcher, Dispatcher::Nearest) {
+            variant(&|s| s.dispatcher = Dispatcher::Nearest);
+        }
+        if !self.parking {
+            variant(&|s| s.parking = true);
+        }
+        if self.arrivals_per_minute > 1.0 {
+            variant(&|s| s.arrivals_per_minute = (s.arrivals_per_minute / 2.0).floor().max(1.0));
+        }
+        if self.groups.share > 0.0 {
+            variant(&|s| s.groups.share = 0.0);
+        }
+        if self.groups.split {
+            variant(&|s| s.groups.split = false);
+        }
+        if self.attendant {
+            variant(&|s| s.attendant = false);
+        }
+        // Each duration on its own back to the default
+        let d = Timing::default();
+        let resets: [fn(&mut Timing, &Timing); 9] = [
+            |t, d| t.tick_seconds = d.tick_seconds,
+            |t, d| t.travel_per_floor_s = d.travel_per_floor_s,
+            |t, d| t.settle_s = d.settle_s,
+            |t, d| t.door_opening_s = d.door_opening_s,
+            |t, d| t.door_open_s = d.door_open_s,
+            |t, d| t.door_closing_s = d.door_closing_s,
+            |t, d| t.boarding_s = d.boarding_s,
+            |t, d| t.idle_timeout_s = d.idle_timeout_s,
+            |t, d| t.max_wait_s = d.max_wait_s,
+        ];
+        for reset in resets {
+            let mut timing = self.timing;
+            reset(&mut timing, &d);
+            if timing != self.timing {
+                variant(&|s| s.timing = timing);
+            }
+        }
+        candidates
+    }
+}
+
+// Runs the scenario with the checker after every step, then stops arrivals and
+// waits for the building to empty
+pub fn run(scenario: &Scenario) -> Result<(), Failure> {
+    let mut building = scenario.building();
+    let fail = |step, message| Failure { step, message };
+    for step in 1..=scenario.steps {
+        building.step();
+        invariants::check(&building).map_err(|m| fail(step, m))?;
+    }
+    building.stop_arrivals();
+    let drain_steps = (DRAIN_SECONDS / scenario.timing.tick_seconds).ceil() as usize;
+    for step in scenario.steps + 1..=scenario.steps + drain_steps {
+        if invariants::undelivered(&building) == 0 {
+            return Ok(());
+        }
+        building.step();
+        invariants::check(&building).map_err(|m| fail(step, m))?;
+    }
+    match invariants::undelivered(&building) {
+        0 => Ok(()),
+        left => Err(fail(scenario.steps + drain_steps, format!("{} passengers not delivered {}s after arrivals stopped", left, DRAIN_SECONDS))),
+    }
+}
+
+// Takes the first simpler variant that still fails until none does
+pub fn shrink(scenario: Scenario, failure: Failure, fails: impl Fn(&Scenario) -> Result<(), Failure>) -> (Scenario, Failure) {
+    let (mut scenario, mut failure) = (scenario, failure);
+    'search: loop {
+        for candidate in scenario.simpler(&failure) {
+            if let Err(f) = fails(&candidate) {
+                (scenario, failure) = (candidate, f);
+                continue 'search;
+            }
+        }
+        return (scenario, failure);
+    }
+}
+
+// `--check [runs]`: random scenarios from `base_seed` on, failures shrunk and printed
+pub fn check_random(runs: u64, base_seed: u64, threads: usize) -> usize {
+    let scenarios: Vec<Scenario> = (base_seed..base_seed + runs).map(Scenario::random).collect();
+    let results = batch::run_parallel(&scenarios, threads, run);
+    let mut failed = 0;
+    for (scenario, result) in scenarios.into_iter().zip(results) {
+        if let Err(failure) = result {
+            failed += 1;
+            let seed = scenario.seed;
+            let (minimal, failure) = shrink(scenario, failure, run);
+            println!("Scenario {} failed at step {}: {}", seed, failure.step, failure.message);
+            println!("  simplest failing variant: {}", minimal);
+            println!("  rerun the original with --check 1 --seed={}", seed);
+        }
+    }
+    println!("{} of {} scenarios kept every invariant", runs as usize - failed, runs);
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CabinState, DoorState, PassengerState};
+    use std::sync::atomic::Ordering;
+
+    fn quiet() {
+        crate::QUIET.store(true, Ordering::Relaxed);
+    }
+
+    // A default building after some traffic, with passengers waiting and riding
+    fn busy_building() -> Building {
+        let mut building = crate::default_building().with_seed(3);
+        building.arrivals_per_minute = 30.0;
+        for _ in 0..60 {
+            building.step();
+        }
+        building
+    }
+
+    #[test]
+    fn random_scenarios_keep_invariants() {
+        quiet();
+        for seed in 0..24 {
+            let mut scenario = Scenario::random(seed);
+            scenario.steps = scenario.steps.min(600);
+            if let Err(failure) = run(&scenario) {
+                let (minimal, failure) = shrink(scenario, failure, run);
+                panic!("step {}: {}\nminimal scenario: {}", failure.step, failure.message, minimal);
+            }
+        }
+    }
+
+    #[test]
+    fn checker_accepts_normal_run() {
+        quiet();
+        let building = busy_building();
+        assert!(invariants::undelivered(&building) > 0);
+        assert_eq!(invariants::check(&building), Ok(()));
+    }
+
+    #[test]
+    fn checker_catches_moving_with_open_doors() {
+        quiet();
+        let mut building = busy_building();
+        let car = &mut building.banks[0].elevators[0];
+        car.cabin_state = CabinState::Moving(0, 2);
+        car.door_state = DoorState::Open;
+        assert!(invariants::check(&building).unwrap_err().contains("doors Open"));
+    }
+
+    #[test]
+    fn checker_catches_lost_and_duplicated_passengers() {
+        quiet();
+        let mut building = busy_building();
+        let waiting = building.banks[0].passengers[0].clone();
+        building.banks[0].passengers.push(waiting.clone());
+        assert!(invariants::check(&building).unwrap_err().contains("2 places"));
+        building.banks[0].passengers.retain(|p| p.id != waiting.id);
+        assert!(invariants::check(&building).unwrap_err().contains("lost"));
+    }
+
+    #[test]
+    fn checker_catches_overload() {
+        quiet();
+        let mut building = busy_building();
+        let mut rider = building.banks[0].passengers[0].clone();
+        rider.state = PassengerState::InCabin;
+        for _ in 0..6 {
+            rider.id = building.passenger_counter;
+            building.passenger_counter += 1;
+            building.banks[0].elevators[0].passengers.push(rider.clone());
+        }
+        assert!(invariants::check(&building).unwrap_err().contains("carries"));
+    }
+
+    #[test]
+    fn shrinking_finds_plainest_failing_scenario() {
+        // Pretend the twin building breaks after 100 steps, whatever else is set
+        let fails = |s: &Scenario| {
+            if s.preset == 4 && s.steps >= 100 {
+                Err(Failure { step: 100, message: "broken".to_string() })
+            } else {
+                Ok(())
+            }
+        };
+        let scenario = Scenario {
+            preset: 4,
+            steps: 1500,
+            dispatcher: Dispatcher::Eta(EtaWeights { wait: 2.0, ride_delay: 0.5 }),
+            parking: false,
+            timing: Timing { tick_seconds: 0.5, door_open_s: 3.0, ..Timing::default() },
+            arrivals_per_minute: 12.0,
+            ..Scenario::random(1)
+        };
+        let failure = fails(&scenario).unwrap_err();
+        let (minimal, _) = shrink(scenario, failure, fails);
+        assert_eq!((minimal.preset, minimal.steps), (4, 100));
+        assert!(matches!(minimal.dispatcher, Dispatcher::Nearest));
+        assert!(minimal.parking);
+        assert_eq!(minimal.timing.tick_seconds, 1.5);
+        assert_eq!(minimal.timing.door_open_s, 1.5);
+        assert_eq!(minimal.arrivals_per_minute, 1.0);
+    }
+}
@@ -0,0 +1,163 @@
+// Average wait per origin floor and simulated time of day. Each boarding adds
+// its wait to the cell of the floor the journey started from and the time the
+// passenger arrived there. The grid can be saved as SVG or PNG and drawn over
+// the building in the viewer.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use ggez::glam::Vec2;
+use ggez::graphics::{self, Canvas, Color, Text};
+use ggez::{Context, GameResult};
+
+use crate::building::Building;
+use crate::tracker::StepTracker;
+use crate::NUM_FLOORS;
+
+const DAY_START_SECONDS: f64 = 7.0 * 3600.0;  // The simulated clock starts at 07:00
+const EMPTY_CELL: [u8; 3] = [230, 230, 230];
+
+pub struct WaitHeatmap {
+    bucket_seconds: f64,
+    tracker: StepTracker,
+    cells: Vec<Vec<(f64, u32)>>,  // Per time bucket and floor, wait sum in seconds and count
+}
+
+impl WaitHeatmap {
+    pub fn new(bucket_minutes: f32) -> Self {
+        WaitHeatmap {
+            bucket_seconds: (bucket_minutes as f64 * 60.0).max(1.0),
+            tracker: StepTracker::default(),
+            cells: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, building: &Building) {
+        for boarding in self.tracker.update(building) {
+            let bucket = (boarding.arrival_s / self.bucket_seconds) as usize;
+            if self.cells.len() <= bucket {
+                self.cells.resize(bucket + 1, vec![(0.0, 0); NUM_FLOORS as usize]);
+            }
+            let cell = &mut self.cells[bucket][boarding.origin as usize];
+            cell.0 += boarding.wait_s as f64;
+            cell.1 += 1;
+        }
+    }
+
+    fn mean(&self, bucket: usize, floor: usize) -> Option<f32> {
+        let (sum, count) = self.cells[bucket][floor];
+        (count > 0).then(|| (sum / count as f64) as f32)
+    }
+
+    fn peak(&self) -> f32 {
+        (0..self.cells.len())
+            .flat_map(|b| (0..NUM_FLOORS as usize).filter_map(move |f| self.mean(b, f)))
+            .fold(1.0, f32::max)
+    }
+
+    fn bucket_label(&self, bucket: usize) -> String {
+        let seconds = DAY_START_SECONDS + bucket as f64 * self.bucket_seconds;
+        let minutes = (seconds / 60.0) as u64;
+        format!("{:02}:{:02}", minutes / 60 % 24, minutes % 60)
+    }
+
+    // Pale yellow for short waits through orange to dark red for the longest
+    fn color(&self, bucket: usize, floor: usize, peak: f32) -> [u8; 3] {
+        let Some(mean) = self.mean(bucket, floor) else {
+            return EMPTY_CELL;
+        };
+        let stops: [[f32; 3]; 3] = [[255.0, 237.0, 160.0], [253.0, 141.0, 60.0], [189.0, 0.0, 38.0]];
+        let t = (mean / peak).clamp(0.0, 1.0) * 2.0;
+        let (from, to, f) = if t < 1.0 { (stops[0], stops[1], t) } else { (stops[1], stops[2], t - 1.0) };
+        [0, 1, 2].map(|i| (from[i] + (to[i] - from[i]) * f).round() as u8)
+    }
+
+    pub fn write_svg(&self, path: &str) -> io::Result<()> {
+        let (cell_w, cell_h, left, top) = (60, 40, 70, 40);
+        let floors = NUM_FLOORS as usize;
+        let width = left + self.cells.len() * cell_w + 20;
+        let height = top + floors * cell_h + 40;
+        let peak = self.peak();
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"12\">", width, height)?;
+        writeln!(out, "<text x=\"{}\" y=\"20\" font-size=\"14\">Average wait in seconds by origin floor and time of day (max {:.1})</text>", left, peak)?;
+        for floor in 0..floors {
+            let y = top + (floors - 1 - floor) * cell_h;
+            writeln!(out, "<text x=\"10\" y=\"{}\">Floor {}</text>", y + cell_h / 2 + 4, floor)?;
+            for bucket in 0..self.cells.len() {
+                let x = left + bucket * cell_w;
+                let [r, g, b] = self.color(bucket, floor, peak);
+                writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"rgb({},{},{})\" stroke=\"white\"/>", x, y, cell_w, cell_h, r, g, b)?;
+                if let Some(mean) = self.mean(bucket, floor) {
+                    writeln!(out, "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{:.1}</text>", x + cell_w / 2, y + cell_h / 2 + 4, mean)?;
+                }
+            }
+        }
+        for bucket in 0..self.cells.len() {
+            let x = left + bucket * cell_w;
+            writeln!(out, "<text x=\"{}\" y=\"{}\">{}</text>", x + 4, top + floors * cell_h + 18, self.bucket_label(bucket))?;
+        }
+        writeln!(out, "</svg>")?;
+        out.flush()
+    }
+
+    // One square block of pixels per cell, top floor first; no labels
+    pub fn write_png(&self, path: &str) -> io::Result<()> {
+        let cell = 20;
+        let floors = NUM_FLOORS as usize;
+        let (width, height) = ((self.cells.len() * cell).max(1), floors * cell);
+        let peak = self.peak();
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            let floor = floors - 1 - y / cell;
+            for x in 0..width {
+                let color = if x / cell < self.cells.len() { self.color(x / cell, floor, peak) } else { EMPTY_CELL };
+                pixels.extend_from_slice(&color);
+            }
+        }
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&pixels).map_err(io::Error::other)
+    }
+
+    pub fn export(&self, prefix: &str) {
+        match self.write_svg(&format!("{}.svg", prefix)).and_then(|_| self.write_png(&format!("{}.png", prefix))) {
+            Ok(()) => println!("Wrote wait heatmap to {}.svg and {}.png", prefix, prefix),
+            Err(e) => println!("Heatmap export to {} failed: {}", prefix, e),
+        }
+    }
+
+    // Cells laid over the building, time running left to right across each floor
+    pub fn draw_overlay(&self, ctx: &mut Context, canvas: &mut Canvas, left: f32, width: f32, floor_y: impl Fn(i32) -> f32) -> GameResult {
+        if self.cells.is_empty() {
+            return Ok(());
+        }
+        let peak = self.peak();
+        let cell_width = width / self.cells.len() as f32;
+        for floor in 0..NUM_FLOORS {
+            let bottom = floor_y(floor);
+            for bucket in 0..self.cells.len() {
+                let [r, g, b] = self.color(bucket, floor as usize, peak);
+                canvas.draw(
+                    &graphics::Mesh::new_rectangle(
+                        ctx,
+                        graphics::DrawMode::fill(),
+                        graphics::Rect::new(left + bucket as f32 * cell_width, bottom - 100.0, cell_width, 100.0),
+                        Color::from_rgba(r, g, b, 170),
+                    )?,
+                    graphics::DrawParam::default(),
+                );
+            }
+        }
+        let caption = format!(
+            "Wait heatmap from {} to {} (max {:.1}s)",
+            self.bucket_label(0),
+            self.bucket_label(self.cells.len()),
+            peak
+        );
+        canvas.draw(&Text::new(caption), graphics::DrawParam::default().dest(Vec2::new(left, 575.0)).color(Color::BLACK));
+        Ok(())
+    }
+}
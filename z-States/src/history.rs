@@ -0,0 +1,35 @@
+// The latest entries of a stream that grows for as long as the simulation runs,
+// with a count of everything ever recorded. Readers remember the count they
+// have seen and pick up what came after it, so long runs keep only a window.
+
+use std::collections::VecDeque;
+
+const HISTORY_LEN: usize = 1000;  // Entries kept; readers look every step, so only a few are ever new
+
+#[derive(Debug, Clone)]
+pub struct History<T> {
+    pub latest: VecDeque<T>,
+    pub total: u64,  // Entries recorded so far, including those no longer kept
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        History { latest: VecDeque::new(), total: 0 }
+    }
+}
+
+impl<T> History<T> {
+    pub fn push(&mut self, entry: T) {
+        if self.latest.len() == HISTORY_LEN {
+            self.latest.pop_front();
+        }
+        self.latest.push_back(entry);
+        self.total += 1;
+    }
+
+    // Entries recorded after the first `seen`, as far as they are still kept
+    pub fn after(&self, seen: u64) -> impl Iterator<Item = &T> {
+        let new = (self.total.saturating_sub(seen) as usize).min(self.latest.len());
+        self.latest.iter().skip(self.latest.len() - new)
+    }
+}
@@ -0,0 +1,99 @@
+// Rules the simulation must keep after every step. `check` looks at the whole
+// building and reports the first broken rule; the harness calls it each tick.
+
+use crate::building::Building;
+use crate::{CabinState, DoorState, PassengerState};
+
+const LOAD_SLACK: f32 = 1e-3;  // Rounding allowed when summing loads
+
+// Every passenger who entered the building is in exactly one place: waiting at a
+// landing, riding a car, or delivered. Only the latest deliveries are kept, so
+// the delivered are checked by their count
+fn check_passengers(building: &Building) -> Result<(), String> {
+    let mut seen = vec![0u32; building.passenger_counter];
+    let mut count = |id: usize, place: &str| -> Result<(), String> {
+        match seen.get_mut(id) {
+            Some(times) => {
+                *times += 1;
+                Ok(())
+            }
+            None => Err(format!("unknown passenger {} {}", id, place)),
+        }
+    };
+    for (b, bank) in building.banks.iter().enumerate() {
+        for passenger in &bank.passengers {
+            if !matches!(passenger.state, PassengerState::Idle(_)) {
+                return Err(format!("passenger {} waiting in bank {} but {:?}", passenger.id, b, passenger.state));
+            }
+            count(passenger.id, "waiting")?;
+        }
+        for (c, elevator) in bank.elevators.iter().enumerate() {
+            for passenger in &elevator.passengers {
+                if passenger.state != PassengerState::InCabin {
+                    return Err(format!("passenger {} riding car {} but {:?}", passenger.id, c, passenger.state));
+                }
+                count(passenger.id, "riding")?;
+            }
+        }
+        for trip in bank.trips.after(0) {
+            count(trip.id, "delivered")?;
+        }
+    }
+    if let Some(id) = seen.iter().position(|&times| times > 1) {
+        return Err(format!("passenger {} found in {} places", id, seen[id]));
+    }
+    let delivered: u64 = building.banks.iter().map(|b| b.trips.total).sum();
+    let accounted = undelivered(building) + delivered as usize;
+    if accounted < building.passenger_counter {
+        return Err(format!("{} passengers lost", building.passenger_counter - accounted));
+    }
+    Ok(())
+}
+
+fn check_cars(building: &Building) -> Result<(), String> {
+    for (b, bank) in building.banks.iter().enumerate() {
+        for (c, elevator) in bank.elevators.iter().enumerate() {
+            let car = format!("bank {} car {}", b, c);
+            if elevator.door_state != DoorState::Closed && !matches!(elevator.cabin_state, CabinState::Holding(_)) {
+                return Err(format!("{} is {:?} with doors {:?}", car, elevator.cabin_state, elevator.door_state));
+            }
+            let position = elevator.position();
+            if position < 0 || position > elevator.max_stop() {
+                return Err(format!("{} at floor {}, outside 0..={}", car, position, elevator.max_stop()));
+            }
+            for deck in 0..elevator.num_decks() {
+                let load_kg = elevator.deck_load_kg(deck);
+                let area_m2 = elevator.deck_area_m2(deck);
+                if load_kg > elevator.rated_load_kg * elevator.boarding_threshold + LOAD_SLACK {
+                    return Err(format!("{} deck {} carries {:.1} kg, rated {:.1} kg", car, deck, load_kg, elevator.rated_load_kg));
+                }
+                if area_m2 > elevator.floor_area_m2 + LOAD_SLACK {
+                    return Err(format!("{} deck {} needs {:.2} m2, has {:.2} m2", car, deck, area_m2, elevator.floor_area_m2));
+                }
+            }
+        }
+        // A hall call belongs to one car at a time
+        let mut calls: Vec<usize> = bank.elevators.iter().flat_map(|e| e.assigned_calls.iter().map(|&(id, _)| id)).collect();
+        calls.sort_unstable();
+        if let Some(pair) = calls.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(format!("bank {} has the call of passenger {} on two cars", b, pair[0]));
+        }
+        for shaft in &bank.shafts {
+            let (lower, upper) = (bank.elevators[shaft.lower].position(), bank.elevators[shaft.upper].position());
+            if upper - lower < shaft.min_separation {
+                return Err(format!("bank {} shaft cars at floors {} and {}, closer than {}", b, lower, upper, shaft.min_separation));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn check(building: &Building) -> Result<(), String> {
+    check_passengers(building)?;
+    check_cars(building)
+}
+
+// Passengers not yet at their final destination
+pub fn undelivered(building: &Building) -> usize {
+    building.banks.iter().map(|b| b.passengers.len() + b.elevators.iter().map(|e| e.passengers.len()).sum::<usize>()).sum()
+}
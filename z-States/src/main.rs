@@ -1,7 +1,61 @@
-use rand::Rng;
-use std::collections::VecDeque;
+use std::sync::atomic::AtomicBool;
+
+// Simulation trace output, silenced for machine-readable modes like `--rl`
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+macro_rules! sim_log {
+    ($($arg:tt)*) => {
+        if !$crate::QUIET.load(std::sync::atomic::Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
+mod batch;
+mod building;
+mod compare;
+mod dashboard;
+mod dispatch;
+mod energy;
+mod events;
+mod harness;
+mod heatmap;
+mod history;
+mod invariants;
+mod metrics;
+mod optimizer;
+mod parking;
+mod prometheus;
+mod rl;
+mod shaft;
+mod snapshot;
+mod timing;
+mod tracker;
+mod trajectory;
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use ggez::{Context, GameResult, graphics::{self, Color, Text}, event};
 use ggez::glam::Vec2;
+use ggez::input::keyboard::{KeyCode, KeyInput};
+use building::{Building, GroupTraffic};
+use dashboard::Dashboard;
+use dispatch::{Dispatcher, EtaWeights, REASSIGN_RATIO};
+use energy::{EnergyMeter, EnergyModel};
+use heatmap::WaitHeatmap;
+use history::History;
+use metrics::Recorder;
+use prometheus::MetricsServer;
+use parking::{LobbyZoneParking, ParkingPolicy};
+use shaft::Shaft;
+use timing::Timing;
+
+const NUM_FLOORS: i32 = 4;
+const LOBBY_FLOOR: i32 = 0;
+const UP_PEAK_WINDOW: usize = 20;  // Recent arrivals looked at to detect up-peak traffic
+const PHASE_EPSILON: f32 = 1e-4;  // Slack for rounding when summing tick lengths against a duration
+const BUSY_ESCALATION_FACTOR: f32 = 2.0;  // Multiple of the wait limit after which cars with riders take overdue calls too
+const PASSENGER_MASS_KG: f32 = 75.0;
+const PASSENGER_AREA_M2: f32 = 0.2;
 
 #[derive(Debug, Clone, PartialEq)]
 enum CabinState {
@@ -19,6 +73,7 @@ enum DoorState {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
 enum PassengerState {
     Idle(i32),
     Entering,
@@ -28,17 +83,101 @@ enum PassengerState {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 enum Direction_ {
     UP,
     DOWN,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ServiceMode {
+    Group,        // Normal operation, car answers hall calls from the dispatcher
+    Independent,  // Car calls only, doors stay open until a car call is entered
+    Attendant,    // Hall calls only while carrying riders, the car only departs on a car call
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Luggage {
+    None,
+    Suitcase,
+    Trolley,
+    Wheelchair,
+}
+
+impl Luggage {
+    fn mass_kg(&self) -> f32 {
+        match self {
+            Luggage::None => 0.0,
+            Luggage::Suitcase => 20.0,
+            Luggage::Trolley => 40.0,
+            Luggage::Wheelchair => 20.0,
+        }
+    }
+
+    fn area_m2(&self) -> f32 {
+        match self {
+            Luggage::None => 0.0,
+            Luggage::Suitcase => 0.15,
+            Luggage::Trolley => 0.4,
+            Luggage::Wheelchair => 0.8,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Passenger {
     id: usize,
     state: PassengerState,
+    #[allow(dead_code)]
     direction: Direction_,
     destination: i32,
+    final_destination: Option<i32>,  // Set while the passenger is on the first leg of a transfer journey
+    mass_kg: f32,
+    luggage: Luggage,
+    deck: usize,  // 0 for the lower deck (and single-deck cars), 1 for the upper deck
+    waited_s: f32,  // Time spent waiting at a landing, summed over all legs
+    ride_s: f32,    // Time spent in a cabin, summed over all legs
+    origin: i32,        // Floor where the journey started
+    arrived_s: f64,     // Simulated time the journey started
+    overdue: bool,  // Waited past the limit, served before everyone else
+    group: Option<usize>,  // Id of the first member of the party travelling together
+}
+
+// A passenger starting their last leg, with the wait summed over all legs
+#[derive(Debug, Clone, Copy)]
+struct Boarding {
+    origin: i32,
+    arrival_s: f64,  // Simulated time the passenger arrived at the origin
+    wait_s: f32,
+}
+
+// Waits of everyone who boarded for their last leg, summed up so that long
+// runs do not keep each one
+#[derive(Debug, Clone, Copy, Default)]
+struct WaitTotals {
+    wait_sum_s: f64,
+    max_wait_s: f32,
+    overdue: u64,  // Boarded after waiting past the escalation limit
+}
+
+impl WaitTotals {
+    fn add(&mut self, wait_s: f32, max_wait_s: f32) {
+        self.wait_sum_s += wait_s as f64;
+        self.max_wait_s = self.max_wait_s.max(wait_s);
+        if wait_s + PHASE_EPSILON >= max_wait_s {
+            self.overdue += 1;
+        }
+    }
+}
+
+// A finished journey, kept for reports and exports
+#[derive(Debug, Clone)]
+struct Trip {
+    id: usize,
+    origin: i32,
+    destination: i32,
+    wait_s: f32,
+    ride_s: f32,
 }
 
 impl Passenger {
@@ -52,9 +191,44 @@ impl Passenger {
             } else {
                 Direction_::DOWN
             },
+            final_destination: None,
+            mass_kg: PASSENGER_MASS_KG,
+            luggage: Luggage::None,
+            deck: 0,
+            waited_s: 0.0,
+            ride_s: 0.0,
+            origin: floor,
+            arrived_s: 0.0,
+            overdue: false,
+            group: None,
         }
     }
 
+    fn via(mut self, transfer_floor: i32) -> Self {
+        self.final_destination = Some(self.destination);
+        self.destination = transfer_floor;
+        self
+    }
+
+    fn with_load(mut self, mass_kg: f32, luggage: Luggage) -> Self {
+        self.mass_kg = mass_kg;
+        self.luggage = luggage;
+        self
+    }
+
+    fn cabin_stop(&self) -> i32 {
+        // Cabin position that brings the passenger's deck to their destination
+        self.destination - self.deck as i32
+    }
+
+    fn load_kg(&self) -> f32 {
+        self.mass_kg + self.luggage.mass_kg()
+    }
+
+    fn area_m2(&self) -> f32 {
+        PASSENGER_AREA_M2 + self.luggage.area_m2()
+    }
+
     //fn destination(&self) -> Option<i32> {
     //    if let PassengerState::ChoosingFloor(dest) = self.state {
     //        Some(dest)
@@ -64,42 +238,216 @@ impl Passenger {
     //}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Elevator {
     cabin_state: CabinState,
     door_state: DoorState,
     passengers: Vec<Passenger>,
     destinations: VecDeque<i32>,
-    max_capacity: usize,
+    rated_load_kg: f32,
+    floor_area_m2: f32,
+    boarding_threshold: f32,  // Fraction of rated load at which boarding stops
+    bypass_threshold: f32,    // Fraction of rated load at which hall calls are skipped
+    #[allow(dead_code)]
     current_direction: Option<Direction_>,
-    door_timer: u32,
+    service_mode: ServiceMode,
+    idle_s: f32,  // Time the car has been standing empty
+    timing: Timing,
+    phase_s: f32,  // Time spent in the current cabin and door state
+    moved_this_stop: u32,  // Passengers who entered or left since the doors opened, each keeps them open longer
+    energy: EnergyMeter,
+    passengers_served: usize,
+    served_floors: BTreeSet<i32>,
+    transferring: Vec<Passenger>,  // Passengers who left at a transfer floor, picked up by the control system
+    finished_trips: Vec<Trip>,     // Passengers who reached their final destination, picked up by the control system
+    double_deck: bool,  // Cabin floor is the lower deck, the upper deck stands one floor above
+    priority_stop: Option<i32>,  // Pickup for an overdue hall call, served before any other stop
+    assigned_calls: Vec<(usize, i32)>,  // Passenger id and pickup stop of each hall call this car has taken on
 }
 
 impl Elevator {
     fn new(starting_floor: i32) -> Self {
+        let rated_load_kg = 200.0;  // Requirement S5, roughly two people
         Elevator {
             cabin_state: CabinState::Standing(starting_floor),
             door_state: DoorState::Closed,
             passengers: vec![],
             destinations: VecDeque::new(),
-            max_capacity: 2,  // Requirement S5
+            rated_load_kg,
+            floor_area_m2: 1.0,
+            boarding_threshold: 1.0,
+            bypass_threshold: 0.8,
             current_direction: None,
-            door_timer: 0,
+            service_mode: ServiceMode::Group,
+            idle_s: 0.0,
+            timing: Timing::default(),
+            phase_s: 0.0,
+            moved_this_stop: 0,
+            energy: EnergyMeter::new(EnergyModel::new(rated_load_kg)),
+            passengers_served: 0,
+            served_floors: (0..NUM_FLOORS).collect(),
+            transferring: Vec::new(),
+            finished_trips: Vec::new(),
+            double_deck: false,
+            priority_stop: None,
+            assigned_calls: Vec::new(),
+        }
+    }
+
+    fn new_double_deck(starting_floor: i32) -> Self {
+        Elevator {
+            double_deck: true,
+            ..Elevator::new(starting_floor)
         }
     }
 
+    fn num_decks(&self) -> usize {
+        if self.double_deck { 2 } else { 1 }
+    }
+
+    fn deck_for(&self, origin_floor: i32) -> usize {
+        // Even floors board the lower deck, odd floors the upper deck
+        if self.double_deck { (origin_floor % 2) as usize } else { 0 }
+    }
+
+    fn max_stop(&self) -> i32 {
+        // Highest cabin position, the upper deck must still be inside the building
+        NUM_FLOORS - self.num_decks() as i32
+    }
+
+    fn pickup_stop(&self, origin_floor: i32) -> i32 {
+        origin_floor - self.deck_for(origin_floor) as i32
+    }
+
+    fn can_carry(&self, from: i32, to: i32) -> bool {
+        let deck = self.deck_for(from) as i32;
+        let stops = 0..=self.max_stop();
+        self.serves(from) && self.serves(to) && stops.contains(&(from - deck)) && stops.contains(&(to - deck))
+    }
+
+    fn boarding_floors(&self, stop: i32) -> Vec<(usize, i32)> {
+        // (deck, floor) pairs where passengers can board while the cabin stands at `stop`
+        (0..self.num_decks())
+            .map(|deck| (deck, stop + deck as i32))
+            .filter(|&(deck, floor)| self.deck_for(floor) == deck)
+            .collect()
+    }
+
+    fn serves(&self, floor: i32) -> bool {
+        self.served_floors.contains(&floor)
+    }
+
+    // Folded from 0.0 rather than summed: an empty f32 sum is -0.0, which prints as "-0"
+    fn current_load_kg(&self) -> f32 {
+        self.passengers.iter().fold(0.0, |total, p| total + p.load_kg())
+    }
+
+    fn deck_load_kg(&self, deck: usize) -> f32 {
+        self.passengers.iter().filter(|p| p.deck == deck).fold(0.0, |total, p| total + p.load_kg())
+    }
+
+    fn deck_area_m2(&self, deck: usize) -> f32 {
+        self.passengers.iter().filter(|p| p.deck == deck).fold(0.0, |total, p| total + p.area_m2())
+    }
+
+    fn can_fit(&self, deck: usize, extra_kg: f32, extra_area_m2: f32) -> bool {
+        // Rated load and floor area apply to each deck separately
+        self.deck_load_kg(deck) + extra_kg <= self.rated_load_kg * self.boarding_threshold
+            && self.deck_area_m2(deck) + extra_area_m2 <= self.floor_area_m2
+    }
+
+    fn can_fit_empty(&self, kg: f32, area_m2: f32) -> bool {
+        kg <= self.rated_load_kg * self.boarding_threshold && area_m2 <= self.floor_area_m2
+    }
+
     fn is_full(&self) -> bool {
-        self.passengers.len() >= self.max_capacity
+        // Full once not even a single passenger without luggage fits on any deck
+        (0..self.num_decks()).all(|deck| !self.can_fit(deck, PASSENGER_MASS_KG, PASSENGER_AREA_M2))
+    }
+
+    fn energy_per_passenger_kwh(&self) -> Option<f32> {
+        if self.passengers_served == 0 {
+            None
+        } else {
+            Some(self.energy.total_kwh() / self.passengers_served as f32)
+        }
+    }
+
+    fn is_bypassing(&self) -> bool {
+        // Full-load bypass: a nearly full car keeps going past hall calls
+        self.current_load_kg() >= self.rated_load_kg * self.num_decks() as f32 * self.bypass_threshold
+    }
+
+    fn position(&self) -> i32 {
+        match self.cabin_state {
+            CabinState::Standing(f) | CabinState::Holding(f) | CabinState::Moving(f, _) => f,
+        }
+    }
+
+    fn next_floor(&self) -> Option<i32> {
+        // Floor the cabin enters on its next step, if it is travelling
+        match self.cabin_state {
+            CabinState::Moving(current, target) if current < target => Some(current + 1),
+            CabinState::Moving(current, target) if current > target => Some(current - 1),
+            _ => None,
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        matches!(self.cabin_state, CabinState::Standing(_))
+            && self.door_state == DoorState::Closed
+            && self.destinations.is_empty()
+            && self.passengers.is_empty()
+    }
+
+    fn accepts_hall_calls(&self) -> bool {
+        match self.service_mode {
+            ServiceMode::Group => true,
+            ServiceMode::Independent => false,
+            // An empty attendant car waits for someone to step in, so it would sit on the call
+            ServiceMode::Attendant => !self.passengers.is_empty(),
+        }
+    }
+
+    fn has_car_call(&self, current_floor: i32) -> bool {
+        self.passengers.iter().any(|p| p.cabin_stop() != current_floor && self.destinations.contains(&p.cabin_stop()))
+    }
+
+    fn set_service_mode(&mut self, mode: ServiceMode) {
+        if self.service_mode == mode {
+            return;
+        }
+        sim_log!("Elevator switching from {:?} to {:?} service", self.service_mode, mode);
+        self.service_mode = mode;
+
+        if mode != ServiceMode::Group {
+            // Drop pickups the dispatcher gave us, keep only floors our passengers asked for
+            let stops: Vec<i32> = self.passengers.iter().map(|p| p.cabin_stop()).collect();
+            self.destinations.retain(|d| stops.contains(d));
+            self.priority_stop = None;
+            self.assigned_calls.clear();
+
+            // Park with open doors until someone enters a car call
+            if let CabinState::Standing(floor) = self.cabin_state {
+                if self.door_state == DoorState::Closed && self.destinations.is_empty() {
+                    self.cabin_state = CabinState::Holding(floor);
+                    self.door_state = DoorState::Opening;
+                }
+            }
+        }
     }
 
     fn sort_destinations(&mut self) {
+        let stops = self.destinations.drain(..).collect();
+        self.destinations = self.sorted_stops(stops);
+    }
+
+    // Stops in the order this car serves them, from where it is now
+    fn sorted_stops(&self, mut dest_vec: Vec<i32>) -> VecDeque<i32> {
         if let Some(current_floor) = match self.cabin_state {
             CabinState::Standing(f) | CabinState::Holding(f) => Some(f),
             CabinState::Moving(from, _) => Some(from),
         } {
-            // Convert destinations to Vec for sorting
-            let mut dest_vec: Vec<i32> = self.destinations.drain(..).collect();
-            
             // Sort based on current direction and position
             let going_up = dest_vec.iter().any(|&d| d > current_floor);
             
@@ -135,9 +483,15 @@ impl Elevator {
                 });
             }
 
-            // Put back into destinations queue
-            self.destinations = VecDeque::from(dest_vec);
+            // An overdue pickup goes before everything else
+            if let Some(stop) = self.priority_stop {
+                if let Some(index) = dest_vec.iter().position(|&d| d == stop) {
+                    dest_vec.remove(index);
+                    dest_vec.insert(0, stop);
+                }
+            }
         }
+        VecDeque::from(dest_vec)
     }
 
     fn add_destination(&mut self, floor: i32) {
@@ -147,14 +501,43 @@ impl Elevator {
         }
     }
 
+    // Gives up a hall call; the pickup stop stays if a rider, another call or the current stop still needs it
+    fn withdraw_call(&mut self, passenger_id: usize, stop: i32) {
+        self.assigned_calls.retain(|&(id, _)| id != passenger_id);
+        let needed = self.priority_stop == Some(stop)
+            || self.assigned_calls.iter().any(|&(_, s)| s == stop)
+            || self.passengers.iter().any(|p| p.cabin_stop() == stop)
+            || self.cabin_state == CabinState::Holding(stop);
+        if !needed {
+            self.destinations.retain(|&d| d != stop);
+        }
+    }
+
     fn step(&mut self) {
+        self.energy.record_standby(self.timing.tick_seconds);
+
+        // Passengers leave as soon as the doors are open, not only once the open time is over
+        if let (CabinState::Holding(floor), DoorState::Open) = (&self.cabin_state, &self.door_state) {
+            let floor = *floor;
+            let exited = self.handle_passenger_exchange(floor);
+            self.moved_this_stop += exited.len() as u32;
+        }
+
+        // Stay in the current state until it has lasted as long as the timing says
+        self.phase_s += self.timing.tick_seconds;
+        let phase_duration = self.timing.phase_seconds(&self.cabin_state, &self.door_state, self.moved_this_stop);
+        if self.phase_s + PHASE_EPSILON < phase_duration {
+            return;
+        }
+        let phase = (self.cabin_state.clone(), self.door_state.clone());
+
         match self.cabin_state.clone() {
             CabinState::Standing(floor) => {
                 if self.door_state == DoorState::Closed {
                     if let Some(&next_floor) = self.destinations.front() {
                         // Check if there's a valid reason to go to this floor
                         let has_waiting_passenger = self.destinations.contains(&next_floor);
-                        let has_passenger_going_there = self.passengers.iter().any(|p| p.destination == next_floor);
+                        let has_passenger_going_there = self.passengers.iter().any(|p| p.cabin_stop() == next_floor);
                         
                         if has_waiting_passenger || has_passenger_going_there {
                             if next_floor == floor {
@@ -176,13 +559,14 @@ impl Elevator {
                 } else {
                     current - 1
                 };
+                self.energy.record_travel(self.current_load_kg(), target > current);
                 if new_floor == target {
                     self.cabin_state = CabinState::Holding(target);
                     // Double check if we still need to stop here
                     let has_waiting_passenger = self.destinations.contains(&target);
-                    let has_passenger_going_there = self.passengers.iter().any(|p| p.destination == target);
+                    let has_passenger_going_there = self.passengers.iter().any(|p| p.cabin_stop() == target);
                     if !has_waiting_passenger && !has_passenger_going_there {
-                        self.destinations.pop_front();
+                        self.destinations.retain(|&d| d != target);
                         self.cabin_state = CabinState::Standing(target);
                     }
                 } else {
@@ -192,21 +576,32 @@ impl Elevator {
             CabinState::Holding(floor) => match self.door_state {
                 DoorState::Opening => {
                     self.door_state = DoorState::Open;
+                    self.energy.record_door_operation();
                 }
                 DoorState::Open => {
-                    self.handle_passenger_exchange(floor);
-                    self.door_state = DoorState::Closing;
+                    // Independent and attendant cars hold their doors until a car call is entered
+                    if self.service_mode == ServiceMode::Group || self.has_car_call(floor) {
+                        self.door_state = DoorState::Closing;
+                    }
                 }
                 DoorState::Closing => {
                     self.door_state = DoorState::Closed;
+                    self.moved_this_stop = 0;
+                    self.energy.record_door_operation();
                     self.cabin_state = CabinState::Standing(floor);
-                    self.destinations.pop_front(); // Remove reached destination
+                    self.destinations.retain(|&d| d != floor); // Remove reached destination
+                    if self.priority_stop == Some(floor) {
+                        self.priority_stop = None;
+                    }
                 }
                 DoorState::Closed => {
                     self.door_state = DoorState::Opening;
                 }
             },
         }
+        if (self.cabin_state.clone(), self.door_state.clone()) != phase {
+            self.phase_s = 0.0;
+        }
         
         // Fix the passenger state checking
         for passenger in &mut self.passengers {
@@ -222,10 +617,9 @@ impl Elevator {
                         let passenger_going_up = passenger.destination > floor;
                         
                         // Add destination if elevator is moving in same direction as passenger wants
-                        if (is_going_up && passenger_going_up) || (!is_going_up && !passenger_going_up) {
-                            if !self.destinations.contains(&floor) {
-                                self.destinations.push_back(floor);
-                            }
+                        if ((is_going_up && passenger_going_up) || (!is_going_up && !passenger_going_up))
+                            && !self.destinations.contains(&floor) {
+                            self.destinations.push_back(floor);
                         }
                     },
                     CabinState::Holding(_) => {} // Do nothing if holding
@@ -237,25 +631,43 @@ impl Elevator {
     fn handle_passenger_exchange(&mut self, current_floor: i32) -> Vec<usize> {
         // Handle exiting passengers
         let mut exited_passengers = Vec::new();
+        let mut transferring = Vec::new();
+        let mut finished_trips = Vec::new();
         self.passengers.retain(|passenger| {
-            if passenger.state == PassengerState::InCabin && passenger.destination == current_floor {
-                println!("Passenger {} exiting at floor {}", passenger.id, current_floor);
+            if passenger.state == PassengerState::InCabin && passenger.cabin_stop() == current_floor {
+                sim_log!("Passenger {} exiting at floor {}", passenger.id, passenger.destination);
                 exited_passengers.push(passenger.id);
+                if let Some(final_destination) = passenger.final_destination {
+                    // Second leg starts from here with another car
+                    let mut next_leg = passenger.clone();
+                    next_leg.state = PassengerState::Idle(passenger.destination);
+                    next_leg.deck = 0;
+                    next_leg.destination = final_destination;
+                    next_leg.final_destination = None;
+                    transferring.push(next_leg);
+                } else {
+                    finished_trips.push(Trip {
+                        id: passenger.id,
+                        origin: passenger.origin,
+                        destination: passenger.destination,
+                        wait_s: passenger.waited_s,
+                        ride_s: passenger.ride_s,
+                    });
+                }
                 false
             } else {
                 true
             }
         });
+        self.passengers_served += exited_passengers.len() - transferring.len();
+        self.transferring.extend(transferring);
+        self.finished_trips.extend(finished_trips);
 
         // Debug print
-        println!("Elevator at floor {} has {} passengers", current_floor, self.passengers.len());
-
-        self.door_timer += 1;
-        if self.door_timer > 5 && !self.is_full() {
-            self.door_state = DoorState::Closing;
-            self.door_timer = 0;
+        if !exited_passengers.is_empty() {
+            sim_log!("Elevator at floor {} has {} passengers", current_floor, self.passengers.len());
         }
-        
+
         exited_passengers
     }
 }
@@ -263,7 +675,17 @@ impl Elevator {
 struct ControlSystem {
     passengers: Vec<Passenger>,
     elevators: Vec<Elevator>,
-    passenger_counter: usize,
+    parking_policy: Box<dyn ParkingPolicy>,
+    recent_origins: VecDeque<i32>,
+    transfer_floors: Vec<i32>,  // Sky lobbies where passengers may change cars
+    handoffs: Vec<Passenger>,  // Passengers whose next leg belongs to another bank
+    shafts: Vec<Shaft>,  // Shafts shared by two cars, cars not listed have a shaft of their own
+    dispatcher: Dispatcher,
+    timing: Timing,
+    boardings: History<Boarding>,  // Passengers who boarded for their last leg
+    waits: WaitTotals,  // Over all of `boardings`, including those no longer kept
+    trips: History<Trip>,  // Passengers delivered to their final destination
+    split_groups: bool,  // Party members may board separately
 }
 
 impl ControlSystem {
@@ -271,40 +693,220 @@ impl ControlSystem {
         let elevators = (0..num_elevators)
             .map(|_| Elevator::new(0))
             .collect();
+        ControlSystem::with_elevators(elevators)
+    }
 
+    fn with_elevators(elevators: Vec<Elevator>) -> Self {
         ControlSystem {
             passengers: Vec::new(),
             elevators,
-            passenger_counter: 0,
+            parking_policy: Box::new(LobbyZoneParking { lobby: LOBBY_FLOOR, num_floors: NUM_FLOORS }),
+            recent_origins: VecDeque::new(),
+            transfer_floors: Vec::new(),
+            handoffs: Vec::new(),
+            shafts: Vec::new(),
+            dispatcher: Dispatcher::Nearest,
+            timing: Timing::default(),
+            boardings: History::default(),
+            waits: WaitTotals::default(),
+            trips: History::default(),
+            split_groups: false,
         }
     }
 
-    fn add_random_passenger(&mut self) {
-        let floor = rand::thread_rng().gen_range(0..4);
-        let mut destination_floor = rand::thread_rng().gen_range(0..4);
-        while floor == destination_floor {
-            destination_floor = rand::thread_rng().gen_range(0..4);
+    // One TWIN shaft with a lower car (floors 0..=top-1) and an upper car (1..=top),
+    // plus a conventional car serving every floor
+    fn twin(min_separation: i32) -> Self {
+        let mut lower = Elevator::new(0);
+        lower.served_floors = (0..NUM_FLOORS - min_separation).collect();
+        let mut upper = Elevator::new(NUM_FLOORS - 1);
+        upper.served_floors = (min_separation..NUM_FLOORS).collect();
+        let mut control_system = ControlSystem::with_elevators(vec![lower, upper, Elevator::new(0)]);
+        control_system.shafts.push(Shaft { lower: 0, upper: 1, min_separation });
+        control_system
+    }
+
+    fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        for elevator in &mut self.elevators {
+            elevator.timing = timing;
         }
-        self.passengers.push(Passenger::new(
-            self.passenger_counter,
-            floor,
-            destination_floor,
-        ));
-        self.passenger_counter += 1;
     }
 
-    fn step(&mut self) {
-        // Add new random passenger with lower probability
-        if rand::thread_rng().gen_bool(0.2) {
-            self.add_random_passenger();
+    fn shaft_of(&self, elevator_index: usize) -> Option<&Shaft> {
+        self.shafts.iter().find(|s| s.contains(elevator_index))
+    }
+
+    // Column each car is drawn in, cars sharing a shaft share a column
+    fn shaft_slots(&self) -> Vec<usize> {
+        let mut slots = Vec::new();
+        let mut next_slot = 0;
+        for i in 0..self.elevators.len() {
+            match self.shaft_of(i) {
+                Some(shaft) if shaft.upper == i && shaft.lower < i => slots.push(slots[shaft.lower]),
+                Some(shaft) if shaft.lower == i && shaft.upper < i => slots.push(slots[shaft.upper]),
+                _ => {
+                    slots.push(next_slot);
+                    next_slot += 1;
+                }
+            }
+        }
+        slots
+    }
+
+    fn num_shaft_slots(&self) -> usize {
+        self.shaft_slots().into_iter().max().map_or(0, |s| s + 1)
+    }
+
+    fn positions(&self) -> Vec<i32> {
+        self.elevators.iter().map(|e| e.position()).collect()
+    }
+
+    fn is_blocked(&self, elevator_index: usize) -> bool {
+        let (Some(shaft), Some(next_floor)) = (self.shaft_of(elevator_index), self.elevators[elevator_index].next_floor()) else {
+            return false;
+        };
+        shaft.blocks(elevator_index, next_floor, &self.positions())
+    }
+
+    fn resolve_shaft_conflicts(&mut self) {
+        for shaft in self.shafts.clone() {
+            let lower_blocked = self.is_blocked(shaft.lower);
+            let upper_blocked = self.is_blocked(shaft.upper);
+
+            // The car that gives way aborts its trip and first moves clear of the other car's target
+            let (yielding, floor) = if lower_blocked && (upper_blocked || self.elevators[shaft.upper].is_idle()) {
+                let CabinState::Moving(_, target) = self.elevators[shaft.lower].cabin_state else { continue };
+                (shaft.upper, target + shaft.min_separation)
+            } else if upper_blocked && self.elevators[shaft.lower].is_idle() {
+                let CabinState::Moving(_, target) = self.elevators[shaft.upper].cabin_state else { continue };
+                (shaft.lower, target - shaft.min_separation)
+            } else {
+                continue;
+            };
+
+            let elevator = &mut self.elevators[yielding];
+            let position = elevator.position();
+            // Already clear or on its way there; the queue may be re-sorted while the cabin travels elsewhere
+            let heading_there = match elevator.cabin_state {
+                CabinState::Moving(_, target) => target == floor,
+                _ => elevator.destinations.front() == Some(&floor),
+            };
+            if position == floor || heading_there {
+                continue;
+            }
+            sim_log!("Elevator {} making way in shared shaft, moving to floor {}", yielding, floor);
+            if let CabinState::Moving(current, _) = elevator.cabin_state {
+                elevator.cabin_state = CabinState::Standing(current);
+            }
+            // First, even if the floor was already queued behind the one it is blocked on
+            elevator.destinations.retain(|&d| d != floor);
+            elevator.destinations.push_front(floor);
+        }
+    }
+
+    fn zoned(num_elevators: usize, zones: Vec<Vec<i32>>, transfer_floors: Vec<i32>) -> Self {
+        let mut control_system = ControlSystem::new(num_elevators);
+        for (elevator, floors) in control_system.elevators.iter_mut().zip(zones) {
+            elevator.served_floors = floors.into_iter().collect();
         }
+        control_system.transfer_floors = transfer_floors;
+        control_system
+    }
+
+    fn has_direct_service(&self, from: i32, to: i32) -> bool {
+        self.elevators.iter().any(|e| e.can_carry(from, to))
+    }
+
+    fn served_floors(&self) -> BTreeSet<i32> {
+        self.elevators.iter().flat_map(|e| e.served_floors.iter().copied()).collect()
+    }
+
+    fn route(&self, from: i32, to: i32) -> Option<Option<i32>> {
+        // None if unreachable, Some(None) for a direct trip, Some(Some(floor)) via a sky lobby
+        if self.has_direct_service(from, to) {
+            return Some(None);
+        }
+        self.transfer_floors
+            .iter()
+            .find(|&&t| self.has_direct_service(from, t) && self.has_direct_service(t, to))
+            .map(|&t| Some(t))
+    }
+
+    fn set_parking_policy(&mut self, policy: Box<dyn ParkingPolicy>) {
+        self.parking_policy = policy;
+    }
+
+    fn is_up_peak(&self) -> bool {
+        // Up-peak when most of the recent arrivals start in the lobby
+        let from_lobby = self.recent_origins.iter().filter(|&&f| f == LOBBY_FLOOR).count();
+        self.recent_origins.len() >= UP_PEAK_WINDOW / 4 && from_lobby * 5 >= self.recent_origins.len() * 3
+    }
 
+    fn park_idle_elevators(&mut self) {
+        let up_peak = self.is_up_peak();
+        let num_elevators = self.elevators.len();
+        for (i, elevator) in self.elevators.iter_mut().enumerate() {
+            if !elevator.is_idle() || elevator.service_mode != ServiceMode::Group {
+                elevator.idle_s = 0.0;
+                continue;
+            }
+            elevator.idle_s += self.timing.tick_seconds;
+            if elevator.idle_s + PHASE_EPSILON < self.timing.idle_timeout_s {
+                continue;
+            }
+            elevator.idle_s = 0.0;
+            if let (Some(home), CabinState::Standing(floor)) = (self.parking_policy.home_floor(i, num_elevators, up_peak), &elevator.cabin_state) {
+                if home != *floor && elevator.serves(home) && home <= elevator.max_stop() {
+                    sim_log!("Elevator {} parking at floor {}", i, home);
+                    elevator.add_destination(home);
+                }
+            }
+        }
+    }
+
+    fn add_passenger(&mut self, passenger: Passenger) -> bool {
+        let PassengerState::Idle(floor) = passenger.state else {
+            return false;
+        };
+        let passenger = match self.route(floor, passenger.destination) {
+            Some(None) => passenger,
+            Some(Some(transfer_floor)) => passenger.via(transfer_floor),
+            None => return false,
+        };
+        self.recent_origins.push_back(floor);
+        if self.recent_origins.len() > UP_PEAK_WINDOW {
+            self.recent_origins.pop_front();
+        }
+        self.passengers.push(passenger);
+        true
+    }
+
+    fn step(&mut self) {
         // First assign passengers to elevators
         self.assign_passengers_to_elevators();
 
+        // Send cars that have been idle for a while to their home floor
+        self.park_idle_elevators();
+
+        // Cars in shared shafts make way for each other before anyone moves
+        self.resolve_shaft_conflicts();
+
         // Then update each elevator
-        for elevator in &mut self.elevators {
-            elevator.step();
+        let mut transferring = Vec::new();
+        for i in 0..self.elevators.len() {
+            let blocked = self.is_blocked(i);
+            let elevator = &mut self.elevators[i];
+            if blocked {
+                // Hold position until the other car in the shaft has moved away
+                elevator.energy.record_standby(self.timing.tick_seconds);
+            } else {
+                elevator.step();
+            }
+            transferring.append(&mut elevator.transferring);
+            for trip in elevator.finished_trips.drain(..) {
+                self.trips.push(trip);
+            }
 
             if let (CabinState::Holding(floor), DoorState::Open) = (&elevator.cabin_state, &elevator.door_state) {
                 if !elevator.is_full() {
@@ -312,93 +914,307 @@ impl ControlSystem {
                     let mut passengers_to_remove = Vec::new();
                     let mut passengers_to_add = Vec::new();
 
-                    // First, identify all passengers that should enter
-                    for (index, passenger) in self.passengers.iter().enumerate() {
-                        if let PassengerState::Idle(p_floor) = passenger.state {
-                            if p_floor == current_floor && passengers_to_add.len() + elevator.passengers.len() < elevator.max_capacity {
-                                passengers_to_remove.push(index);
-                                let mut new_passenger = passenger.clone();
-                                new_passenger.state = PassengerState::InCabin;
-                                passengers_to_add.push(new_passenger);
+                    // First, identify all passengers that should enter, deck by deck
+                    for (deck, deck_floor) in elevator.boarding_floors(current_floor) {
+                        let mut added_kg = 0.0;
+                        let mut added_area_m2 = 0.0;
+                        for (index, passenger) in self.passengers.iter().enumerate() {
+                            if let PassengerState::Idle(p_floor) = passenger.state {
+                                if p_floor != deck_floor || passengers_to_remove.contains(&index) || !elevator.can_carry(p_floor, passenger.destination) {
+                                    continue;
+                                }
+                                // A party boards together or not at all
+                                let (party, kg, area_m2) = ControlSystem::boarding_party(&self.passengers, index, elevator, self.split_groups);
+                                if elevator.can_fit(deck, added_kg + kg, added_area_m2 + area_m2) {
+                                    added_kg += kg;
+                                    added_area_m2 += area_m2;
+                                    for member in party {
+                                        passengers_to_remove.push(member);
+                                        let mut new_passenger = self.passengers[member].clone();
+                                        new_passenger.state = PassengerState::InCabin;
+                                        new_passenger.deck = deck;
+                                        passengers_to_add.push(new_passenger);
+                                    }
+                                }
                             }
                         }
                     }
 
                     // Remove passengers from waiting list in reverse order
+                    passengers_to_remove.sort_unstable();
                     for &index in passengers_to_remove.iter().rev() {
-                        let passenger = &self.passengers[index];
-                        println!("Passenger {} entering elevator at floor {}", passenger.id, current_floor);
                         self.passengers.remove(index);
                     }
 
                     // Add passengers to elevator and their destinations
                     for passenger in passengers_to_add {
-                        elevator.add_destination(passenger.destination);  // Add destination before adding passenger
+                        sim_log!("Passenger {} entering elevator at floor {}", passenger.id, current_floor + passenger.deck as i32);
+                        if passenger.final_destination.is_none() {
+                            self.waits.add(passenger.waited_s, self.timing.max_wait_s);
+                            self.boardings.push(Boarding { origin: passenger.origin, arrival_s: passenger.arrived_s, wait_s: passenger.waited_s });
+                        }
+                        elevator.add_destination(passenger.cabin_stop());  // Add destination before adding passenger
                         elevator.passengers.push(passenger);
+                        elevator.moved_this_stop += 1;
                     }
                 }
             }
         }
+
+        // Everyone still at a landing waited one more step
+        let tick_seconds = self.timing.tick_seconds;
+        for passenger in &mut self.passengers {
+            passenger.waited_s += tick_seconds;
+        }
+        for passenger in self.elevators.iter_mut().flat_map(|e| e.passengers.iter_mut()) {
+            passenger.ride_s += tick_seconds;
+        }
+
+        // Passengers who changed cars wait again here, unless another bank takes them further
+        for passenger in transferring {
+            if !self.add_passenger(passenger.clone()) {
+                self.handoffs.push(passenger);
+            }
+        }
+    }
+
+    // Counts `steps` steps in which nothing happens but time passing
+    fn idle(&mut self, steps: u64) {
+        let seconds = self.timing.tick_seconds * steps as f32;
+        for elevator in &mut self.elevators {
+            elevator.energy.record_standby(seconds);
+            elevator.phase_s += seconds;
+            if elevator.is_idle() && elevator.service_mode == ServiceMode::Group {
+                elevator.idle_s += seconds;
+            } else {
+                elevator.idle_s = 0.0;
+            }
+            for passenger in &mut elevator.passengers {
+                passenger.ride_s += seconds;
+            }
+        }
+        for passenger in &mut self.passengers {
+            passenger.waited_s += seconds;
+        }
+    }
+
+    fn assign_call(&mut self, floor: i32, elevator_index: usize) -> bool {
+        match self.elevators.get_mut(elevator_index) {
+            Some(elevator) if elevator.serves(floor) && elevator.accepts_hall_calls() => {
+                let stop = elevator.pickup_stop(floor);
+                elevator.add_destination(stop);
+                true
+            }
+            _ => false,
+        }
     }
 
     fn assign_passengers_to_elevators(&mut self) {
-        for passenger in &self.passengers {
+        // Flag hall calls that have waited past the limit
+        let max_wait_s = self.timing.max_wait_s;
+        for passenger in &mut self.passengers {
             if let PassengerState::Idle(floor) = passenger.state {
-                // Find best elevator for this passenger
-                let best_elevator = self.elevators
-                    .iter_mut()
-                    .filter(|e| !e.is_full())
-                    .min_by_key(|e| {
-                        match e.cabin_state {
-                            CabinState::Standing(e_floor) | CabinState::Holding(e_floor) => {
-                                (e_floor - floor).abs()
-                            },
-                            CabinState::Moving(from, to) => {
-                                // Check if passenger is "on the way"
-                                let elevator_going_up = to > from;
-                                let passenger_going_up = passenger.destination > floor;
-                                
-                                if elevator_going_up == passenger_going_up {  // Same direction
-                                    if elevator_going_up {
-                                        // Going up: passenger should be between current position and destination
-                                        if floor >= from && floor <= to {
-                                            0  // Perfect match!
-                                        } else {
-                                            i32::MAX  // Wrong direction
-                                        }
-                                    } else {
-                                        // Going down: passenger should be between destination and current position
-                                        if floor <= from && floor >= to {
-                                            0  // Perfect match!
-                                        } else {
-                                            i32::MAX  // Wrong direction
-                                        }
-                                    }
-                                } else {
-                                    i32::MAX  // Wrong direction
-                                }
-                            }
-                        }
-                    });
+                if !passenger.overdue && passenger.waited_s + PHASE_EPSILON >= max_wait_s {
+                    passenger.overdue = true;
+                    sim_log!("Passenger {} waited {:.0}s at floor {}, escalating hall call", passenger.id, passenger.waited_s, floor);
+                }
+            }
+        }
+        if let Dispatcher::External = self.dispatcher {
+            return;
+        }
+        let mut elevators = std::mem::take(&mut self.elevators);
+        self.assign_hall_calls(&mut elevators);
+        self.elevators = elevators;
+    }
+
+    // Waiting passengers who board together with `passengers[index]`, with their
+    // load and floor area: the whole party, unless parties may split or this one
+    // is too large for the empty car
+    fn boarding_party(passengers: &[Passenger], index: usize, elevator: &Elevator, split: bool) -> (Vec<usize>, f32, f32) {
+        let passenger = &passengers[index];
+        let alone = (vec![index], passenger.load_kg(), passenger.area_m2());
+        let Some(group) = passenger.group.filter(|_| !split) else {
+            return alone;
+        };
+        let party: Vec<usize> = (0..passengers.len())
+            .filter(|&i| passengers[i].group == Some(group) && passengers[i].state == passenger.state && passengers[i].destination == passenger.destination)
+            .collect();
+        let (kg, area_m2) = party.iter().fold((0.0, 0.0), |(kg, area), &i| (kg + passengers[i].load_kg(), area + passengers[i].area_m2()));
+        if elevator.can_fit_empty(kg, area_m2) {
+            (party, kg, area_m2)
+        } else {
+            alone
+        }
+    }
+
+    // Gives each waiting passenger's call to the cheapest of `elevators`, which are
+    // this bank's cars or a copy of them, and keeps it there unless another car is
+    // clearly better
+    fn assign_hall_calls(&self, elevators: &mut [Elevator]) {
+        // Cars in a shared shaft prefer calls they can reach without waiting for the other car
+        let positions: Vec<i32> = elevators.iter().map(|e| e.position()).collect();
+        let free_ranges: Vec<(i32, i32)> = (0..elevators.len())
+            .map(|i| self.shaft_of(i).map_or((i32::MIN, i32::MAX), |s| s.free_range(i, &positions)))
+            .collect();
 
-                if let Some(elevator) = best_elevator {
-                    elevator.add_destination(floor);  // Add pickup floor as destination
+        // Calls of passengers who boarded or left the bank are given up, and so are
+        // all calls of a car that no longer takes any, like an attendant car that emptied
+        let waiting: HashSet<usize> = self.passengers.iter().filter(|p| matches!(p.state, PassengerState::Idle(_))).map(|p| p.id).collect();
+        for elevator in elevators.iter_mut() {
+            let hands_back = !elevator.accepts_hall_calls();
+            if hands_back {
+                elevator.priority_stop = None;
+            }
+            for (id, stop) in elevator.assigned_calls.clone() {
+                if hands_back || !waiting.contains(&id) {
+                    elevator.withdraw_call(id, stop);
                 }
             }
         }
+
+        let mut owners = ControlSystem::call_owners(elevators);
+        self.escalate_overdue_calls(elevators, &free_ranges, &mut owners);
+
+        for (index, passenger) in self.passengers.iter().enumerate() {
+            let PassengerState::Idle(floor) = passenger.state else { continue };
+            let current = owners.get(&passenger.id).copied();
+            if current.is_some_and(|c| elevators[c].priority_stop == Some(elevators[c].pickup_stop(floor))) {
+                continue;  // Escalated, stays with its car
+            }
+            // Cost of each car that can take the call
+            let cost = |i: usize, e: &Elevator| {
+                let deck = e.deck_for(floor);
+                let (_, kg, area_m2) = ControlSystem::boarding_party(&self.passengers, index, e, self.split_groups);
+                let takes = !e.is_full() && !e.is_bypassing() && e.accepts_hall_calls()
+                    && e.can_carry(floor, passenger.destination)
+                    // Room on the deck they would board for them and their party, or the car reopens for them forever
+                    && e.can_fit(deck, kg, area_m2);
+                takes.then(|| {
+                    // Double-deck cars pick up with the cabin at the lower deck position
+                    let stop = e.pickup_stop(floor);
+                    let (low, high) = free_ranges[i];
+                    let shaft_penalty = if stop < low || stop > high { NUM_FLOORS as f32 } else { 0.0 };
+                    // A party that may split still prefers a car with room for all of them
+                    let (_, party_kg, party_area_m2) = ControlSystem::boarding_party(&self.passengers, index, e, false);
+                    let party_penalty = if e.can_fit(deck, party_kg, party_area_m2) { 0.0 } else { NUM_FLOORS as f32 };
+                    self.dispatcher.cost(e, stop, passenger.destination) + shaft_penalty + party_penalty
+                })
+            };
+            let best = elevators.iter().enumerate().filter_map(|(i, e)| Some((i, cost(i, e)?))).min_by(|a, b| a.1.total_cmp(&b.1));
+            let Some((best, best_cost)) = best else { continue };
+
+            // A call only moves to a clearly cheaper car, or when its car can no longer take it
+            let target = match current {
+                Some(c) if c != best && cost(c, &elevators[c]).is_some_and(|kept| best_cost >= kept * REASSIGN_RATIO) => c,
+                _ => best,
+            };
+            if let Some(previous) = current.filter(|&c| c != target) {
+                let stop = elevators[previous].pickup_stop(floor);
+                elevators[previous].withdraw_call(passenger.id, stop);
+            }
+            let elevator = &mut elevators[target];
+            let stop = elevator.pickup_stop(floor);
+            if current != Some(target) {
+                elevator.assigned_calls.push((passenger.id, stop));
+            }
+            elevator.add_destination(stop);  // Add pickup floor as destination
+        }
+    }
+
+    // Car index holding each passenger's hall call, by passenger id
+    fn call_owners(elevators: &[Elevator]) -> HashMap<usize, usize> {
+        elevators.iter().enumerate().flat_map(|(i, e)| e.assigned_calls.iter().map(move |&(id, _)| (id, i))).collect()
+    }
+
+    // Wait after which an overdue call may also go to a car with riders aboard
+    fn busy_escalation_s(&self) -> f32 {
+        self.timing.max_wait_s * BUSY_ESCALATION_FACTOR
+    }
+
+    // Overdue calls, oldest first, each go to the nearest free car, whatever the
+    // dispatcher thinks, as that car's next stop. Cars with riders aboard are left
+    // alone so an overloaded building does not detour them from call to call,
+    // until the call has waited busy_escalation_s and no car is free; then the
+    // nearest car with room for the caller takes it. Each such detour adds a rider,
+    // so a car fills up and heads off to deliver before it is detoured for long
+    fn escalate_overdue_calls(&self, elevators: &mut [Elevator], free_ranges: &[(i32, i32)], owners: &mut HashMap<usize, usize>) {
+        let mut overdue: Vec<usize> = (0..self.passengers.len())
+            .filter(|&i| self.passengers[i].overdue && matches!(self.passengers[i].state, PassengerState::Idle(_)))
+            .collect();
+        overdue.sort_by(|&a, &b| {
+            let (a, b) = (&self.passengers[a], &self.passengers[b]);
+            b.waited_s.total_cmp(&a.waited_s).then(a.id.cmp(&b.id))
+        });
+        for index in overdue {
+            let passenger = &self.passengers[index];
+            let PassengerState::Idle(floor) = passenger.state else { continue };
+            let busy_cars_too = passenger.waited_s + PHASE_EPSILON >= self.busy_escalation_s();
+            let has_room = |e: &Elevator| {
+                let (_, kg, area_m2) = ControlSystem::boarding_party(&self.passengers, index, e, self.split_groups);
+                !e.is_full() && !e.is_bypassing() && e.can_fit(e.deck_for(floor), kg, area_m2)
+            };
+            let takes = |e: &Elevator| e.accepts_hall_calls() && e.can_carry(floor, passenger.destination);
+            if elevators.iter().any(|e| takes(e) && e.priority_stop == Some(e.pickup_stop(floor))) {
+                continue;  // Already escalated
+            }
+            let nearest = elevators
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| takes(e) && e.priority_stop.is_none() && (e.passengers.is_empty() || (busy_cars_too && has_room(e))))
+                .map(|(i, e)| {
+                    let stop = e.pickup_stop(floor);
+                    let (low, high) = free_ranges[i];
+                    let shaft_penalty = if stop < low || stop > high { NUM_FLOORS } else { 0 };
+                    (i, !e.passengers.is_empty(), (e.position() - stop).abs() + shaft_penalty)
+                })
+                .min_by_key(|&(i, busy, distance)| (busy, distance, i));
+            if let Some((i, _, _)) = nearest {
+                let previous = owners.insert(passenger.id, i);
+                if let Some(previous) = previous.filter(|&c| c != i) {
+                    let stop = elevators[previous].pickup_stop(floor);
+                    elevators[previous].withdraw_call(passenger.id, stop);
+                }
+                let elevator = &mut elevators[i];
+                let stop = elevator.pickup_stop(floor);
+                if previous != Some(i) {
+                    elevator.assigned_calls.push((passenger.id, stop));
+                }
+                elevator.priority_stop = Some(stop);
+                if !elevator.destinations.contains(&stop) {
+                    elevator.destinations.push_back(stop);
+                }
+                elevator.sort_destinations();
+            }
+        }
     }
 }
 
 struct GameState {
-    control_system: ControlSystem,
+    building: Building,
     step_timer: f32,
+    parking_enabled: bool,
+    recorder: Recorder,
+    metrics: Option<MetricsServer>,
+    dashboard: Dashboard,
+    heatmap: WaitHeatmap,
+    show_heatmap: bool,
 }
 
 impl GameState {
-    fn new() -> Self {
+    fn new(building: Building, metrics: Option<MetricsServer>, heatmap: WaitHeatmap) -> Self {
+        // A loaded snapshot may have parking switched off
+        let parking_enabled = building.banks.iter().any(|b| b.parking_policy.name() != "none");
+        let recorder = Recorder::new(&building);
         GameState {
-            control_system: ControlSystem::new(3),
+            building,
             step_timer: 0.0,
+            parking_enabled,
+            recorder,
+            metrics,
+            dashboard: Dashboard::default(),
+            heatmap,
+            show_heatmap: false,
         }
     }
 }
@@ -406,8 +1222,14 @@ impl GameState {
 impl event::EventHandler<ggez::GameError> for GameState {
     fn update(&mut self, ctx: &mut Context) -> GameResult {
         self.step_timer += ctx.time.delta().as_secs_f32();
-        if self.step_timer >= 1.5 {  // Slightly faster than 2.0 but still slow enough to see
-            self.control_system.step();
+        if self.step_timer >= self.building.timing().tick_seconds {
+            self.building.step();
+            self.recorder.record(&self.building);
+            self.dashboard.update(&self.building);
+            self.heatmap.update(&self.building);
+            if let Some(metrics) = &mut self.metrics {
+                metrics.update(&self.building);
+            }
             self.step_timer = 0.0;
         }
         Ok(())
@@ -423,7 +1245,7 @@ impl event::EventHandler<ggez::GameError> for GameState {
         let building_width = 400.0;
 
         // Draw floors
-        for floor in 0..4 {
+        for floor in 0..NUM_FLOORS {
             let y = 500.0 - (floor as f32 * floor_height);
             canvas.draw(
                 &graphics::Mesh::new_line(
@@ -437,7 +1259,7 @@ impl event::EventHandler<ggez::GameError> for GameState {
         }
 
         // Draw floor numbers
-        for floor in 0..4 {
+        for floor in 0..NUM_FLOORS {
             let y = 500.0 - (floor as f32 * floor_height);
             let floor_text = Text::new(format!("Floor {}", floor));
             canvas.draw(
@@ -448,18 +1270,37 @@ impl event::EventHandler<ggez::GameError> for GameState {
             );
         }
 
+        // Wait heatmap behind the cars, one band per floor
+        if self.show_heatmap {
+            self.heatmap.draw_overlay(ctx, &mut canvas, building_left, building_width, |floor| 500.0 - floor as f32 * floor_height)?;
+        }
+
         // Draw elevators with improved passenger visualization
         let elevator_width = 60.0;
-        let elevator_spacing = (building_width - 3.0 * elevator_width) / 4.0;
+        // Cars sharing a shaft are drawn in one column, each bank starts a new group of columns
+        let mut slots = Vec::new();
+        let mut bank_starts = Vec::new();
+        let mut num_slots = 0;
+        for (b, bank) in self.building.banks.iter().enumerate() {
+            if b > 0 {
+                bank_starts.push(num_slots);
+            }
+            slots.extend(bank.shaft_slots().into_iter().map(|slot| num_slots + slot));
+            num_slots += bank.num_shaft_slots();
+        }
+        let elevator_spacing = (building_width - num_slots as f32 * elevator_width) / (num_slots as f32 + 1.0);
 
-        for (i, elevator) in self.control_system.elevators.iter().enumerate() {
-            let elevator_x = building_left + elevator_spacing + (i as f32 * (elevator_width + elevator_spacing));
+        for (i, elevator) in self.building.elevators().enumerate() {
+            let slot = slots[i];
+            let elevator_x = building_left + elevator_spacing + (slot as f32 * (elevator_width + elevator_spacing));
+            // The second car of a shared shaft lists its details below the building
+            let info_y = if slots[..i].contains(&slot) { 525.0 } else { 25.0 };
             
             // Simplified elevator position calculation - no interpolation
             let elevator_y = match &elevator.cabin_state {
                 CabinState::Standing(floor) | CabinState::Holding(floor) => 
                     500.0 - (*floor as f32 * floor_height),
-                CabinState::Moving(from, to) => 
+                CabinState::Moving(from, _to) => 
                     // Just show elevator at the 'from' floor - no animation
                     500.0 - (*from as f32 * floor_height),
             };
@@ -479,11 +1320,16 @@ impl event::EventHandler<ggez::GameError> for GameState {
             );
 
             // Draw elevator state (debug info)
-            let state_text = match &elevator.cabin_state {
+            let mut state_text = match &elevator.cabin_state {
                 CabinState::Standing(floor) => format!("Standing {}", floor),
                 CabinState::Moving(from, to) => format!("Moving {}→{}", from, to),
                 CabinState::Holding(floor) => format!("Holding {}", floor),
             };
+            match elevator.service_mode {
+                ServiceMode::Group => {}
+                ServiceMode::Independent => state_text.push_str(" IND"),
+                ServiceMode::Attendant => state_text.push_str(" ATT"),
+            }
             
             canvas.draw(
                 &Text::new(state_text),
@@ -497,7 +1343,28 @@ impl event::EventHandler<ggez::GameError> for GameState {
             canvas.draw(
                 &Text::new(dest_text),
                 graphics::DrawParam::default()
-                    .dest(Vec2::new(elevator_x - 30.0, 80.0))
+                    .dest(Vec2::new(elevator_x - 30.0, info_y + 55.0))
+                    .color(Color::BLACK),
+            );
+
+            // Draw current load against rated load
+            let load_text = format!("{:.0}/{:.0}kg", elevator.current_load_kg(), elevator.rated_load_kg);
+            canvas.draw(
+                &Text::new(load_text),
+                graphics::DrawParam::default()
+                    .dest(Vec2::new(elevator_x - 30.0, info_y + 35.0))
+                    .color(Color::BLACK),
+            );
+
+            // Draw energy used so far and per passenger served
+            let energy_text = match elevator.energy_per_passenger_kwh() {
+                Some(per_passenger) => format!("{:.3}kWh\n{:.4}/pax", elevator.energy.total_kwh(), per_passenger),
+                None => format!("{:.3}kWh", elevator.energy.total_kwh()),
+            };
+            canvas.draw(
+                &Text::new(energy_text),
+                graphics::DrawParam::default()
+                    .dest(Vec2::new(elevator_x - 30.0, info_y))
                     .color(Color::BLACK),
             );
 
@@ -515,6 +1382,20 @@ impl event::EventHandler<ggez::GameError> for GameState {
                 draw_param,
             );
 
+            // Mark the floors this car stops at
+            for floor in &elevator.served_floors {
+                let y = 500.0 - (*floor as f32 * floor_height);
+                canvas.draw(
+                    &graphics::Mesh::new_rectangle(
+                        ctx,
+                        graphics::DrawMode::fill(),
+                        graphics::Rect::new(elevator_x - 25.0, y - 2.0, 50.0, 4.0),
+                        Color::new(0.5, 0.5, 0.5, 1.0),
+                    )?,
+                    draw_param,
+                );
+            }
+
             // Draw elevator cabin
             let elevator_color = if elevator.door_state == DoorState::Open {
                 Color::GREEN
@@ -522,15 +1403,19 @@ impl event::EventHandler<ggez::GameError> for GameState {
                 Color::BLUE
             };
 
-            canvas.draw(
-                &graphics::Mesh::new_rectangle(
-                    ctx,
-                    graphics::DrawMode::fill(),
-                    graphics::Rect::new(elevator_x - 20.0, elevator_y - 40.0, 40.0, 80.0),
-                    elevator_color,
-                )?,
-                draw_param,
-            );
+            // Double-deck cars get a second cabin one floor above the first
+            for deck in 0..elevator.num_decks() {
+                let deck_y = elevator_y - deck as f32 * floor_height;
+                canvas.draw(
+                    &graphics::Mesh::new_rectangle(
+                        ctx,
+                        graphics::DrawMode::fill(),
+                        graphics::Rect::new(elevator_x - 20.0, deck_y - 40.0, 40.0, 80.0),
+                        elevator_color,
+                    )?,
+                    draw_param,
+                );
+            }
 
             // Draw elevator number
             let elevator_text = Text::new(format!("E{}", i));
@@ -542,15 +1427,19 @@ impl event::EventHandler<ggez::GameError> for GameState {
             );
 
             // Draw passengers in elevator with destination indicators
-            for (p_idx, passenger) in elevator.passengers.iter().enumerate().take(elevator.max_capacity) {
+            let mut deck_counts = [0usize; 2];
+            for passenger in &elevator.passengers {
+                let p_idx = deck_counts[passenger.deck];
+                deck_counts[passenger.deck] += 1;
                 let passenger_x = elevator_x - 10.0 + (p_idx as f32 * 20.0);
+                let passenger_y = elevator_y - passenger.deck as f32 * floor_height;
                 
                 // Draw passenger dot
                 canvas.draw(
                     &graphics::Mesh::new_circle(
                         ctx,
                         graphics::DrawMode::fill(),
-                        Vec2::new(passenger_x, elevator_y),
+                        Vec2::new(passenger_x, passenger_y),
                         5.0,
                         0.1,
                         Color::BLACK,
@@ -563,14 +1452,35 @@ impl event::EventHandler<ggez::GameError> for GameState {
                 canvas.draw(
                     &dest_text,
                     graphics::DrawParam::default()
-                        .dest(Vec2::new(passenger_x - 5.0, elevator_y + 10.0))
+                        .dest(Vec2::new(passenger_x - 5.0, passenger_y + 10.0))
                         .color(Color::BLACK),
                 );
             }
         }
 
+        // Separate banks into their own shaft groups
+        for (b, &start) in bank_starts.iter().enumerate() {
+            let x = building_left + start as f32 * (elevator_width + elevator_spacing) + elevator_spacing / 2.0;
+            canvas.draw(
+                &graphics::Mesh::new_line(
+                    ctx,
+                    &[Vec2::new(x, 520.0), Vec2::new(x, 100.0)],
+                    3.0,
+                    Color::BLACK,
+                )?,
+                draw_param,
+            );
+            canvas.draw(
+                &Text::new(format!("Bank {}", b + 1)),
+                graphics::DrawParam::default()
+                    .dest(Vec2::new(x + 5.0, 510.0))
+                    .color(Color::BLACK),
+            );
+        }
+
         // Draw waiting passengers with destination indicators
-        for passenger in &self.control_system.passengers {
+        let multiple_banks = self.building.banks.len() > 1;
+        for (b, passenger) in self.building.banks.iter().enumerate().flat_map(|(b, bank)| bank.passengers.iter().map(move |p| (b, p))) {
             if let PassengerState::Idle(floor) = passenger.state {
                 let y = 500.0 - (floor as f32 * floor_height);
                 
@@ -588,7 +1498,11 @@ impl event::EventHandler<ggez::GameError> for GameState {
                 );
 
                 // Draw destination indicator
-                let dest_text = Text::new(format!("→{}", passenger.destination));
+                let dest_text = if multiple_banks {
+                    Text::new(format!("B{}→{}", b, passenger.destination))
+                } else {
+                    Text::new(format!("→{}", passenger.destination))
+                };
                 canvas.draw(
                     &dest_text,
                     graphics::DrawParam::default()
@@ -598,17 +1512,495 @@ impl event::EventHandler<ggez::GameError> for GameState {
             }
         }
 
+        // Running statistics to the right of the building
+        self.dashboard.draw(ctx, &mut canvas, &self.building, 610.0)?;
+
         canvas.finish(ctx)?;
         Ok(())
     }
+
+    fn key_down_event(&mut self, _ctx: &mut Context, input: KeyInput, _repeated: bool) -> GameResult {
+        // E exports everything recorded so far in this session, including the trajectory diagram
+        if input.keycode == Some(KeyCode::E) {
+            self.recorder.export("session");
+            trajectory::export(&self.recorder, "session_trajectory.svg");
+            return Ok(());
+        }
+
+        // S saves the whole simulation state to continue from later with `--load`
+        if input.keycode == Some(KeyCode::S) {
+            snapshot::save(&self.building, "session_snapshot.txt");
+            return Ok(());
+        }
+
+        // H shows or hides the wait heatmap
+        if input.keycode == Some(KeyCode::H) {
+            self.show_heatmap = !self.show_heatmap;
+            return Ok(());
+        }
+
+        // P toggles idle-car parking on and off
+        if input.keycode == Some(KeyCode::P) {
+            self.parking_enabled = !self.parking_enabled;
+            self.building.set_parking(self.parking_enabled);
+            return Ok(());
+        }
+
+        // Keys 1-3 cycle the matching car through group, independent and attendant service
+        let elevator_index = match input.keycode {
+            Some(KeyCode::Key1) => 0,
+            Some(KeyCode::Key2) => 1,
+            Some(KeyCode::Key3) => 2,
+            _ => return Ok(()),
+        };
+        if let Some(elevator) = self.building.elevator_mut(elevator_index) {
+            let next_mode = match elevator.service_mode {
+                ServiceMode::Group => ServiceMode::Independent,
+                ServiceMode::Independent => ServiceMode::Attendant,
+                ServiceMode::Attendant => ServiceMode::Group,
+            };
+            elevator.set_service_mode(next_mode);
+        }
+        Ok(())
+    }
+}
+
+// Low-rise car, express car to the sky lobby on floor 2 and a high-rise car above it
+fn sky_lobby_building() -> Building {
+    Building::single(ControlSystem::zoned(3, vec![vec![0, 1, 2], vec![0, 2], vec![2, 3]], vec![2]))
+}
+
+// Two banks with their own dispatchers meeting on floor 2, entrances from the
+// street on floor 0 and from a bridge on floor 3
+fn two_bank_building() -> Building {
+    let low_bank = ControlSystem::zoned(2, vec![vec![0, 1, 2], vec![0, 1, 2]], vec![]);
+    let high_bank = ControlSystem::zoned(1, vec![vec![2, 3]], vec![]);
+    Building::new(vec![low_bank, high_bank], vec![0, 3], 0.5)
+}
+
+// A TWIN shaft with two cars one floor apart next to a conventional car
+fn twin_building() -> Building {
+    Building::single(ControlSystem::twin(1))
+}
+
+// Two double-deck cars plus a single-deck car for trips the decks cannot reach
+fn double_deck_building() -> Building {
+    Building::single(ControlSystem::with_elevators(vec![
+        Elevator::new_double_deck(0),
+        Elevator::new_double_deck(0),
+        Elevator::new(0),
+    ]))
+}
+
+// Optional outputs of a headless run besides the printed report
+struct HeadlessOutputs<'a> {
+    export: Option<&'a str>,      // Prefix for the CSV and JSON export
+    trajectory: Option<&'a str>,  // Path of the space-time diagram
+    heatmap: Option<(&'a str, WaitHeatmap)>,
+    metrics: Option<MetricsServer>,
+    events: bool,  // Jump between events instead of running every step
+    snapshot: Option<&'a str>,  // Where to save the final state
+}
+
+fn run_headless(mut building: Building, steps: usize, outputs: HeadlessOutputs) {
+    let HeadlessOutputs { export, trajectory, heatmap, mut metrics, events, snapshot } = outputs;
+    let mut recorder = Recorder::new(&building);
+    let (heatmap_prefix, mut heatmap) = heatmap.unzip();
+    let every_step = export.is_some() || trajectory.is_some() || metrics.is_some();
+    if events && every_step {
+        println!("Exports, trajectories and live metrics sample every step, running in tick mode");
+    }
+    let mut event_stats = None;
+    if events && !every_step {
+        let mut stats = events::EventStats::default();
+        let mut done = 0;
+        while done < steps as u64 {
+            done += events::advance(&mut building, steps as u64 - done, &mut stats);
+            if let Some(heatmap) = &mut heatmap {
+                heatmap.update(&building);
+            }
+        }
+        event_stats = Some(stats);
+    } else {
+        for _ in 0..steps {
+            building.step();
+            if let Some(heatmap) = &mut heatmap {
+                heatmap.update(&building);
+            }
+            if export.is_some() || trajectory.is_some() {
+                recorder.record(&building);
+            }
+            if let Some(metrics) = &mut metrics {
+                metrics.update(&building);
+            }
+        }
+    }
+
+    let (average_wait, max_wait) = building.wait_stats();
+    println!("--- Report after {} steps ---", steps);
+    println!("Simulated {:.0} seconds", building.clock_seconds());
+    println!("Average wait {:.1}s, maximum wait {:.1}s", average_wait, max_wait);
+    println!("Passengers who waited past the {:.0}s escalation limit: {}", building.timing().max_wait_s, building.overdue_passengers());
+    let mut total_kwh = 0.0;
+    let mut total_served = 0;
+    for (i, elevator) in building.elevators().enumerate() {
+        let kwh = elevator.energy.total_kwh();
+        total_kwh += kwh;
+        total_served += elevator.passengers_served;
+        match elevator.energy_per_passenger_kwh() {
+            Some(per_passenger) => println!("Elevator {}: {:.3} kWh, {} passengers served, {:.4} kWh per passenger", i, kwh, elevator.passengers_served, per_passenger),
+            None => println!("Elevator {}: {:.3} kWh, no passengers served", i, kwh),
+        }
+    }
+    if total_served > 0 {
+        println!("All elevators: {:.3} kWh, {:.4} kWh per passenger", total_kwh, total_kwh / total_served as f32);
+    } else {
+        println!("All elevators: {:.3} kWh", total_kwh);
+    }
+    if let Some(stats) = event_stats {
+        stats.report();
+    }
+    if let Some(prefix) = export {
+        recorder.export(prefix);
+    }
+    if let (Some(prefix), Some(heatmap)) = (heatmap_prefix, heatmap) {
+        heatmap.export(prefix);
+    }
+    if let Some(path) = trajectory {
+        trajectory::export(&recorder, path);
+    }
+    if let Some(path) = snapshot {
+        snapshot::save(&building, path);
+    }
+}
+
+fn default_building() -> Building {
+    Building::single(ControlSystem::new(3))
+}
+
+fn building_preset(args: &[String]) -> fn() -> Building {
+    if args.iter().any(|a| a == "--zoned") {
+        sky_lobby_building
+    } else if args.iter().any(|a| a == "--banks") {
+        two_bank_building
+    } else if args.iter().any(|a| a == "--double-deck") {
+        double_deck_building
+    } else if args.iter().any(|a| a == "--twin") {
+        twin_building
+    } else {
+        default_building
+    }
+}
+
+// Value of an option written as `--name=VALUE`
+fn flag_value(args: &[String], prefix: &str) -> Option<usize> {
+    args.iter().find_map(|a| a.strip_prefix(prefix)).and_then(|v| v.parse().ok())
+}
+
+// `--threads=N`, otherwise one thread per core
+fn thread_count(args: &[String]) -> usize {
+    flag_value(args, "--threads=").unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+}
+
+// Durations in seconds, e.g. `--tick=0.5 --door-open=3`; anything not given keeps its value in `defaults`
+fn parse_timing(args: &[String], defaults: Timing) -> Timing {
+    let seconds = |prefix: &str| args.iter().find_map(|a| a.strip_prefix(prefix)).and_then(|v| v.parse::<f32>().ok()).filter(|v| *v >= 0.0);
+    Timing {
+        tick_seconds: seconds("--tick=").filter(|v| *v > 0.0).unwrap_or(defaults.tick_seconds),
+        travel_per_floor_s: seconds("--travel=").unwrap_or(defaults.travel_per_floor_s),
+        settle_s: seconds("--settle=").unwrap_or(defaults.settle_s),
+        door_opening_s: seconds("--door-opening=").unwrap_or(defaults.door_opening_s),
+        door_open_s: seconds("--door-open=").unwrap_or(defaults.door_open_s),
+        door_closing_s: seconds("--door-closing=").unwrap_or(defaults.door_closing_s),
+        boarding_s: seconds("--boarding=").unwrap_or(defaults.boarding_s),
+        idle_timeout_s: seconds("--idle-timeout=").unwrap_or(defaults.idle_timeout_s),
+        max_wait_s: seconds("--max-wait=").unwrap_or(defaults.max_wait_s),
+    }
+}
+
+// `--groups=SHARE` and `--split-groups`; anything not given keeps its value in `defaults`
+fn parse_groups(args: &[String], defaults: GroupTraffic) -> GroupTraffic {
+    let share = args.iter().find_map(|a| a.strip_prefix("--groups=")).and_then(|v| v.parse::<f64>().ok()).filter(|v| (0.0..=1.0).contains(v));
+    GroupTraffic {
+        share: share.unwrap_or(defaults.share),
+        split: defaults.split || args.iter().any(|a| a == "--split-groups"),
+    }
+}
+
+fn parse_dispatcher(args: &[String]) -> Option<Dispatcher> {
+    let option = args.iter().find_map(|a| a.strip_prefix("--eta"))?;
+    let mut weights = EtaWeights::default();
+    if let Some(text) = option.strip_prefix('=') {
+        let values: Vec<f32> = text.split(',').filter_map(|v| v.trim().parse().ok()).collect();
+        if let [wait, ride_delay] = values[..] {
+            weights = EtaWeights { wait, ride_delay };
+        } else {
+            println!("Ignoring malformed ETA weights '{}', expected --eta=WAIT,RIDE", text);
+        }
+    }
+    Some(Dispatcher::Eta(weights))
 }
 
 fn main() -> GameResult {
+    // `--headless [steps]` runs the simulation without a window and prints a report,
+    // `--export=PREFIX` also writes the recorded metrics as CSV and JSON,
+    // `--metrics=ADDRESS` serves live metrics in the Prometheus text format,
+    // `--trajectory=PATH` writes the space-time diagram of every car as SVG,
+    // `--heatmap=PREFIX` writes the wait heatmap as SVG and PNG, `--heatmap-minutes=N` sets its buckets,
+    // `--zoned`, `--banks`, `--double-deck` and `--twin` swap the default building for another setup,
+    // `--eta[=WAIT,RIDE]` switches to the ETA dispatcher with optional cost weights,
+    // `--rl [episode steps]` serves the learning environment on stdin/stdout,
+    // `--optimize [steps]` searches dispatch and parking settings for the chosen building,
+    // `--batch [steps]` runs both dispatchers under every traffic profile over many seeds,
+    // `--compare A B [steps]` runs two configurations on the same seeds and tests the differences,
+    // `--tick=`, `--travel=`, `--settle=`, `--door-opening=`, `--door-open=`, `--door-closing=`,
+    // `--boarding=` and `--idle-timeout=` set the simulated durations in seconds,
+    // `--max-wait=` sets how long a hall call may wait before it is escalated,
+    // `--groups=SHARE` makes that share of arrivals parties, `--split-groups` lets parties board separately,
+    // `--events` runs headless, batch, compare and optimizer runs on the discrete-event engine,
+    // `--snapshot=PATH` saves the state after a headless run, `--load=PATH` starts from a saved state
+    // `--check [runs]` runs random scenarios under the invariant checker and shrinks any failure
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let build = building_preset(&args);
+    let timing = parse_timing(&args, Timing::default());
+    let groups = parse_groups(&args, GroupTraffic::default());
+    if args.iter().any(|a| a == "--check") {
+        let runs = args.iter().find_map(|a| a.parse().ok()).unwrap_or(100);
+        QUIET.store(true, std::sync::atomic::Ordering::Relaxed);
+        let failed = harness::check_random(runs, flag_value(&args, "--seed=").unwrap_or(0) as u64, thread_count(&args));
+        if failed > 0 {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.iter().any(|a| a == "--rl") {
+        // The agent places calls for one bank, so presets with several banks cannot be driven
+        if build().banks.len() > 1 || args.iter().any(|a| a.starts_with("--load=")) {
+            println!("--rl needs a single-bank building and cannot start from --load");
+            std::process::exit(1);
+        }
+        let config = rl::RlConfig {
+            build,
+            timing,
+            groups,
+            episode_steps: args.iter().find_map(|a| a.parse().ok()).unwrap_or(1000),
+        };
+        QUIET.store(true, std::sync::atomic::Ordering::Relaxed);
+        rl::serve(config);
+        return Ok(());
+    }
+    if args.iter().any(|a| a == "--optimize") {
+        let config = optimizer::OptimizerConfig {
+            population: flag_value(&args, "--population=").unwrap_or(16),
+            generations: flag_value(&args, "--generations=").unwrap_or(8),
+            seeds: flag_value(&args, "--seeds=").unwrap_or(4) as u64,
+            steps: args.iter().find_map(|a| a.parse().ok()).unwrap_or(1000),
+            threads: thread_count(&args),
+            timing,
+            groups,
+            events: args.iter().any(|a| a == "--events"),
+        };
+        if config.seeds == 0 || config.population == 0 {
+            println!("--optimize needs --seeds=1 and --population=1 or more");
+            std::process::exit(1);
+        }
+        QUIET.store(true, std::sync::atomic::Ordering::Relaxed);
+        optimizer::run(build, &config);
+        return Ok(());
+    }
+    if let Some(position) = args.iter().position(|a| a == "--compare") {
+        let traffic_name = args.iter().find_map(|a| a.strip_prefix("--traffic=")).unwrap_or("normal");
+        let Some(traffic) = batch::TrafficProfile::from_name(traffic_name) else {
+            println!("Unknown traffic profile '{}', expected light, normal or heavy", traffic_name);
+            std::process::exit(1);
+        };
+        let specs: Vec<&String> = args[position + 1..].iter().filter(|a| !a.starts_with("--")).take(2).collect();
+        let [spec_a, spec_b] = specs[..] else {
+            println!("Usage: --compare CONFIG_A CONFIG_B [steps]");
+            std::process::exit(1);
+        };
+        let scenarios = compare::parse_config(spec_a, traffic).and_then(|a| Ok((a, compare::parse_config(spec_b, traffic)?)));
+        let (a, b) = match scenarios {
+            Ok(pair) => pair,
+            Err(message) => {
+                println!("Invalid configuration: {}", message);
+                std::process::exit(1);
+            }
+        };
+        let config = batch::BatchConfig {
+            seeds: flag_value(&args, "--seeds=").unwrap_or(30) as u64,
+            base_seed: flag_value(&args, "--seed=").unwrap_or(0) as u64,
+            steps: args.iter().find_map(|a| a.parse().ok()).unwrap_or(1000),
+            threads: thread_count(&args),
+            timing,
+            groups,
+            events: args.iter().any(|a| a == "--events"),
+            per_run: false,
+        };
+        // The t-test needs at least two paired runs
+        if config.seeds < 2 {
+            println!("--compare needs --seeds=2 or more");
+            std::process::exit(1);
+        }
+        QUIET.store(true, std::sync::atomic::Ordering::Relaxed);
+        compare::run(build, &a, &b, &config);
+        return Ok(());
+    }
+    if args.iter().any(|a| a == "--batch") {
+        let config = batch::BatchConfig {
+            seeds: flag_value(&args, "--seeds=").unwrap_or(100) as u64,
+            base_seed: flag_value(&args, "--seed=").unwrap_or(0) as u64,
+            steps: args.iter().find_map(|a| a.parse().ok()).unwrap_or(1000),
+            threads: thread_count(&args),
+            timing,
+            groups,
+            events: args.iter().any(|a| a == "--events"),
+            per_run: args.iter().any(|a| a == "--per-run"),
+        };
+        let eta_weights = match parse_dispatcher(&args) {
+            Some(Dispatcher::Eta(weights)) => weights,
+            _ => EtaWeights::default(),
+        };
+        QUIET.store(true, std::sync::atomic::Ordering::Relaxed);
+        batch::run(build, &batch::default_scenarios(eta_weights), &config);
+        return Ok(());
+    }
+
+    let mut building = match args.iter().find_map(|a| a.strip_prefix("--load=")) {
+        Some(path) => match snapshot::load(path) {
+            Ok(building) => {
+                println!("Loaded snapshot at {:.0}s from {}", building.clock_seconds(), path);
+                building
+            }
+            Err(message) => {
+                println!("Cannot load snapshot: {}", message);
+                return Ok(());
+            }
+        },
+        None => build(),
+    };
+    // Timing flags override what a snapshot was saved with
+    building.set_timing(parse_timing(&args, building.timing()));
+    building.set_groups(parse_groups(&args, building.groups()));
+    if let Some(dispatcher) = parse_dispatcher(&args) {
+        building.set_dispatcher(dispatcher);
+    }
+    let metrics = match args.iter().find_map(|a| a.strip_prefix("--metrics=")) {
+        Some(address) => match MetricsServer::start(address) {
+            Ok(server) => {
+                println!("Serving metrics on http://{}/metrics", address);
+                Some(server)
+            }
+            Err(e) => {
+                println!("Cannot serve metrics on {}: {}", address, e);
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+    let heatmap = WaitHeatmap::new(flag_value(&args, "--heatmap-minutes=").unwrap_or(15) as f32);
+    if args.iter().any(|a| a == "--headless") {
+        let steps = args.iter().find_map(|a| a.parse().ok()).unwrap_or(1000);
+        let outputs = HeadlessOutputs {
+            export: args.iter().find_map(|a| a.strip_prefix("--export=")),
+            trajectory: args.iter().find_map(|a| a.strip_prefix("--trajectory=")),
+            heatmap: args.iter().find_map(|a| a.strip_prefix("--heatmap=")).map(|prefix| (prefix, heatmap)),
+            metrics,
+            events: args.iter().any(|a| a == "--events"),
+            snapshot: args.iter().find_map(|a| a.strip_prefix("--snapshot=")),
+        };
+        run_headless(building, steps, outputs);
+        return Ok(());
+    }
+
     let cb = ggez::ContextBuilder::new("Elevator Simulation", "Your Name")
         .window_setup(ggez::conf::WindowSetup::default().title("Elevator Simulation"))
-        .window_mode(ggez::conf::WindowMode::default().dimensions(600.0, 600.0));
+        .window_mode(ggez::conf::WindowMode::default().dimensions(900.0, 600.0));
     
     let (ctx, event_loop) = cb.build()?;
-    let state = GameState::new();
+    let state = GameState::new(building, metrics, heatmap);
     event::run(ctx, event_loop, state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Default building without arrivals, with `waiting` queued in the first bank
+    fn building_with(waiting: Vec<Passenger>) -> Building {
+        QUIET.store(true, std::sync::atomic::Ordering::Relaxed);
+        let mut building = default_building().with_seed(1);
+        building.stop_arrivals();
+        building.passenger_counter = waiting.iter().map(|p| p.id + 1).max().unwrap_or(0);
+        building.banks[0].passengers.extend(waiting);
+        building
+    }
+
+    fn rider(id: usize, origin: i32, destination: i32) -> Passenger {
+        let mut rider = Passenger::new(id, origin, destination);
+        rider.state = PassengerState::InCabin;
+        rider
+    }
+
+    #[test]
+    fn overdue_call_goes_first_on_nearest_free_car() {
+        let mut waiting = Passenger::new(1, 3, 0);
+        waiting.waited_s = Timing::default().max_wait_s;
+        let mut building = building_with(vec![waiting]);
+        let bank = &mut building.banks[0];
+        // Car 0 stands at the call but has a rider, car 1 is the nearest free car
+        bank.elevators[0].cabin_state = CabinState::Standing(3);
+        bank.elevators[0].passengers.push(rider(0, 3, 0));
+        bank.elevators[0].add_destination(0);
+        bank.elevators[1].cabin_state = CabinState::Standing(1);
+
+        building.step();
+        let bank = &mut building.banks[0];
+        assert!(bank.passengers[0].overdue);
+        assert_eq!(bank.elevators[1].priority_stop, Some(3));
+        assert_eq!(bank.elevators[0].priority_stop, None);
+        bank.elevators[1].add_destination(0);
+        assert_eq!(bank.elevators[1].destinations.front(), Some(&3));
+    }
+
+    #[test]
+    fn call_moves_only_to_clearly_better_car() {
+        let mut building = building_with(vec![Passenger::new(0, 3, 0)]);
+        building.step();
+        let has_stop = |b: &Building| -> Vec<bool> { b.banks[0].elevators.iter().map(|e| e.destinations.contains(&3)).collect() };
+        assert_eq!(has_stop(&building), [true, false, false]);
+        building.step();
+        assert_eq!(has_stop(&building), [true, false, false]);
+
+        // A car right at the call is clearly better, the first car gives the stop up
+        building.banks[0].elevators[2].cabin_state = CabinState::Standing(3);
+        building.step();
+        let bank = &building.banks[0];
+        let has_call: Vec<bool> = bank.elevators.iter().map(|e| e.assigned_calls.iter().any(|&(id, _)| id == 0)).collect();
+        assert_eq!(has_call, [false, false, true]);
+        assert!(!bank.elevators[0].destinations.contains(&3));
+    }
+
+    #[test]
+    fn party_boards_together_unless_it_may_split() {
+        let board = |split: bool| -> usize {
+            let party = (1..=2).map(|id| {
+                let mut member = Passenger::new(id, 1, 3).with_load(70.0, Luggage::None);
+                member.group = Some(1);
+                member
+            });
+            let mut building = building_with(party.collect());
+            building.set_timing(Timing { door_open_s: 15.0, ..Timing::default() });
+            building.set_groups(GroupTraffic { share: 0.0, split });
+            // Car 0 holds at floor 1 with room for one of the two party members
+            let car = &mut building.banks[0].elevators[0];
+            car.cabin_state = CabinState::Holding(1);
+            car.door_state = DoorState::Open;
+            car.passengers.push(rider(0, 1, 0).with_load(90.0, Luggage::None));
+            building.step();
+            building.banks[0].elevators[0].passengers.len() - 1
+        };
+        assert_eq!(board(false), 0);
+        assert_eq!(board(true), 1);
+    }
+}
@@ -0,0 +1,212 @@
+// Per-tick samples of a running building for analysis outside the simulator.
+// `record` is called after every step; the export writes three CSV tables
+// (queues, cars, passengers) and one JSON document holding the same data.
+//
+//   PREFIX_queues.csv      tick, time_s, floor, waiting
+//   PREFIX_cars.csv        tick, time_s, car, position, cabin, target, door, load_kg, passengers
+//   PREFIX_passengers.csv  id, origin, destination, wait_s, ride_s
+//   PREFIX.json            {"ticks": [{"tick", "time_s", "queues", "cars"}], "passengers": [...]}
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::building::Building;
+use crate::{CabinState, DoorState, PassengerState, Trip};
+
+#[derive(Debug, Clone)]
+pub struct CarSample {
+    pub cabin: CabinState,
+    pub door: DoorState,
+    pub load_kg: f32,
+    pub passengers: usize,
+    pub travel: f32,  // Share of the current one-floor move done, 0 unless moving
+}
+
+impl CarSample {
+    pub fn position(&self) -> i32 {
+        match self.cabin {
+            CabinState::Standing(f) | CabinState::Holding(f) | CabinState::Moving(f, _) => f,
+        }
+    }
+
+    // Height in floors, part way to the next floor while moving
+    pub fn height(&self) -> f32 {
+        match self.cabin {
+            CabinState::Moving(from, to) => from as f32 + (to - from).signum() as f32 * self.travel,
+            CabinState::Standing(f) | CabinState::Holding(f) => f as f32,
+        }
+    }
+
+    // Floor the car is moving to
+    pub fn target(&self) -> Option<i32> {
+        match self.cabin {
+            CabinState::Moving(_, to) => Some(to),
+            _ => None,
+        }
+    }
+}
+
+// A passenger appearing at a landing and, once a car took them, when that was
+#[derive(Debug, Clone)]
+pub struct HallCall {
+    pub floor: i32,
+    pub arrival_tick: usize,
+    pub served_tick: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TickSample {
+    pub tick: usize,
+    pub time_s: f64,  // Simulated clock after the step
+    pub queues: Vec<usize>,  // Waiting passengers per floor
+    pub cars: Vec<CarSample>,
+}
+
+pub struct Recorder {
+    pub ticks: Vec<TickSample>,
+    pub hall_calls: Vec<HallCall>,
+    pub trips: Vec<Trip>,  // Passengers delivered while recording
+    waiting: HashMap<usize, usize>,  // Passenger id to their open entry in `hall_calls`
+    seen_trips: Vec<u64>,  // Per bank, deliveries already copied into `trips` or made before recording
+}
+
+fn cabin_name(state: &CabinState) -> &'static str {
+    match state {
+        CabinState::Standing(_) => "standing",
+        CabinState::Moving(_, _) => "moving",
+        CabinState::Holding(_) => "holding",
+    }
+}
+
+fn door_name(state: &DoorState) -> &'static str {
+    match state {
+        DoorState::Closed => "closed",
+        DoorState::Opening => "opening",
+        DoorState::Open => "open",
+        DoorState::Closing => "closing",
+    }
+}
+
+impl Recorder {
+    // Starts recording at the building's current state, so a loaded snapshot's trips stay out of the export
+    pub fn new(building: &Building) -> Self {
+        Recorder {
+            ticks: Vec::new(),
+            hall_calls: Vec::new(),
+            trips: Vec::new(),
+            waiting: HashMap::new(),
+            seen_trips: building.banks.iter().map(|b| b.trips.total).collect(),
+        }
+    }
+
+    pub fn record(&mut self, building: &Building) {
+        let tick = self.ticks.len() + 1;
+        let queues = building.waiting_per_floor();
+        let mut still_waiting = HashMap::new();
+        for passenger in building.banks.iter().flat_map(|b| b.passengers.iter()) {
+            if let PassengerState::Idle(floor) = passenger.state {
+                let call = self.waiting.remove(&passenger.id).unwrap_or_else(|| {
+                    self.hall_calls.push(HallCall { floor, arrival_tick: tick, served_tick: None });
+                    self.hall_calls.len() - 1
+                });
+                still_waiting.insert(passenger.id, call);
+            }
+        }
+        // Whoever is no longer waiting was picked up during this step
+        for (_, call) in self.waiting.drain() {
+            self.hall_calls[call].served_tick = Some(tick);
+        }
+        self.waiting = still_waiting;
+
+        for (bank, seen) in building.banks.iter().zip(self.seen_trips.iter_mut()) {
+            self.trips.extend(bank.trips.after(*seen).cloned());
+            *seen = bank.trips.total;
+        }
+
+        let cars = building
+            .elevators()
+            .map(|e| CarSample {
+                cabin: e.cabin_state.clone(),
+                door: e.door_state.clone(),
+                load_kg: e.current_load_kg(),
+                passengers: e.passengers.len(),
+                travel: match e.cabin_state {
+                    CabinState::Moving(_, _) => (e.phase_s / e.timing.travel_per_floor_s).min(1.0),
+                    _ => 0.0,
+                },
+            })
+            .collect();
+        self.ticks.push(TickSample { tick, time_s: building.clock_seconds(), queues, cars });
+    }
+
+    pub fn write_csv(&self, prefix: &str) -> io::Result<()> {
+        let mut queues = BufWriter::new(File::create(format!("{}_queues.csv", prefix))?);
+        writeln!(queues, "tick,time_s,floor,waiting")?;
+        let mut cars = BufWriter::new(File::create(format!("{}_cars.csv", prefix))?);
+        writeln!(cars, "tick,time_s,car,position,cabin,target,door,load_kg,passengers")?;
+        for sample in &self.ticks {
+            for (floor, waiting) in sample.queues.iter().enumerate() {
+                writeln!(queues, "{},{:.2},{},{}", sample.tick, sample.time_s, floor, waiting)?;
+            }
+            for (car, c) in sample.cars.iter().enumerate() {
+                let target = c.target().map_or(String::new(), |t| t.to_string());
+                writeln!(cars, "{},{:.2},{},{},{},{},{},{:.1},{}", sample.tick, sample.time_s, car, c.position(), cabin_name(&c.cabin), target, door_name(&c.door), c.load_kg, c.passengers)?;
+            }
+        }
+        queues.flush()?;
+        cars.flush()?;
+
+        let mut passengers = BufWriter::new(File::create(format!("{}_passengers.csv", prefix))?);
+        writeln!(passengers, "id,origin,destination,wait_s,ride_s")?;
+        for trip in &self.trips {
+            writeln!(passengers, "{},{},{},{:.2},{:.2}", trip.id, trip.origin, trip.destination, trip.wait_s, trip.ride_s)?;
+        }
+        passengers.flush()
+    }
+
+    pub fn write_json(&self, prefix: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(format!("{}.json", prefix))?);
+        writeln!(out, "{{\"ticks\": [")?;
+        for (i, sample) in self.ticks.iter().enumerate() {
+            let queues = sample.queues.iter().map(|q| q.to_string()).collect::<Vec<_>>().join(", ");
+            let cars = sample
+                .cars
+                .iter()
+                .map(|c| {
+                    format!(
+                        "{{\"position\": {}, \"cabin\": \"{}\", \"target\": {}, \"door\": \"{}\", \"load_kg\": {:.1}, \"passengers\": {}}}",
+                        c.position(),
+                        cabin_name(&c.cabin),
+                        c.target().map_or("null".to_string(), |t| t.to_string()),
+                        door_name(&c.door),
+                        c.load_kg,
+                        c.passengers
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let separator = if i + 1 < self.ticks.len() { "," } else { "" };
+            writeln!(out, "  {{\"tick\": {}, \"time_s\": {:.2}, \"queues\": [{}], \"cars\": [{}]}}{}", sample.tick, sample.time_s, queues, cars, separator)?;
+        }
+        writeln!(out, "], \"passengers\": [")?;
+        for (i, trip) in self.trips.iter().enumerate() {
+            let separator = if i + 1 < self.trips.len() { "," } else { "" };
+            writeln!(
+                out,
+                "  {{\"id\": {}, \"origin\": {}, \"destination\": {}, \"wait_s\": {:.2}, \"ride_s\": {:.2}}}{}",
+                trip.id, trip.origin, trip.destination, trip.wait_s, trip.ride_s, separator
+            )?;
+        }
+        writeln!(out, "]}}")?;
+        out.flush()
+    }
+
+    // Both formats, reporting where the files went
+    pub fn export(&self, prefix: &str) {
+        match self.write_csv(prefix).and_then(|_| self.write_json(prefix)) {
+            Ok(()) => println!("Exported {} ticks to {}_*.csv and {}.json", self.ticks.len(), prefix, prefix),
+            Err(e) => println!("Export to {} failed: {}", prefix, e),
+        }
+    }
+}
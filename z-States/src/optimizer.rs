@@ -0,0 +1,187 @@
+// Genetic search over dispatch weights and parking settings. Every candidate is
+// run headless over the same seeds, scored on average wait, energy and maximum
+// wait, and the non-dominated candidates seen over all generations are reported.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::batch;
+use crate::building::{Building, GroupTraffic};
+use crate::dispatch::{Dispatcher, EtaWeights};
+use crate::timing::Timing;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate {
+    pub weights: EtaWeights,
+    pub parking: bool,
+    pub idle_timeout_s: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Score {
+    pub average_wait: f32,
+    pub energy_kwh: f32,
+    pub max_wait: f32,
+}
+
+impl Score {
+    fn objectives(&self) -> [f32; 3] {
+        [self.average_wait, self.energy_kwh, self.max_wait]
+    }
+
+    // At least as good everywhere and strictly better somewhere
+    fn dominates(&self, other: &Score) -> bool {
+        let (a, b) = (self.objectives(), other.objectives());
+        a.iter().zip(&b).all(|(x, y)| x <= y) && a.iter().zip(&b).any(|(x, y)| x < y)
+    }
+}
+
+pub struct OptimizerConfig {
+    pub population: usize,
+    pub generations: usize,
+    pub seeds: u64,
+    pub steps: usize,
+    pub threads: usize,
+    pub timing: Timing,
+    pub groups: GroupTraffic,
+    pub events: bool,
+}
+
+impl Candidate {
+    fn random(rng: &mut StdRng) -> Self {
+        Candidate {
+            weights: EtaWeights {
+                wait: rng.gen_range(0.1..3.0),
+                ride_delay: rng.gen_range(0.0..3.0),
+            },
+            parking: rng.gen_bool(0.5),
+            idle_timeout_s: rng.gen_range(1.5..=45.0),
+        }
+    }
+
+    fn crossover(&self, other: &Candidate, rng: &mut StdRng) -> Self {
+        Candidate {
+            weights: EtaWeights {
+                wait: if rng.gen_bool(0.5) { self.weights.wait } else { other.weights.wait },
+                ride_delay: if rng.gen_bool(0.5) { self.weights.ride_delay } else { other.weights.ride_delay },
+            },
+            parking: if rng.gen_bool(0.5) { self.parking } else { other.parking },
+            idle_timeout_s: if rng.gen_bool(0.5) { self.idle_timeout_s } else { other.idle_timeout_s },
+        }
+    }
+
+    fn mutate(mut self, rng: &mut StdRng) -> Self {
+        if rng.gen_bool(0.3) {
+            self.weights.wait = (self.weights.wait + rng.gen_range(-0.5..0.5)).clamp(0.1, 3.0);
+        }
+        if rng.gen_bool(0.3) {
+            self.weights.ride_delay = (self.weights.ride_delay + rng.gen_range(-0.5..0.5)).clamp(0.0, 3.0);
+        }
+        if rng.gen_bool(0.1) {
+            self.parking = !self.parking;
+        }
+        if rng.gen_bool(0.3) {
+            self.idle_timeout_s = (self.idle_timeout_s + rng.gen_range(-7.5..=7.5)).clamp(1.5, 45.0);
+        }
+        self
+    }
+
+    fn apply(&self, building: &mut Building, timing: Timing) {
+        building.set_timing(Timing { idle_timeout_s: self.idle_timeout_s, ..timing });
+        building.set_dispatcher(Dispatcher::Eta(self.weights));
+        building.set_parking(self.parking);
+    }
+}
+
+// Scores every candidate as the mean over all seeds, spreading the runs over threads
+fn evaluate(build: fn() -> Building, candidates: &[Candidate], config: &OptimizerConfig) -> Vec<Score> {
+    let jobs: Vec<(usize, u64)> = (0..candidates.len())
+        .flat_map(|c| (0..config.seeds).map(move |seed| (c, seed)))
+        .collect();
+    let results = batch::run_parallel(&jobs, config.threads, |&(c, seed)| {
+        let mut building = build().with_seed(seed);
+        candidates[c].apply(&mut building, config.timing);
+        building.set_groups(config.groups);
+        batch::run_once(building, seed, config.steps, config.events)
+    });
+
+    results
+        .chunks(config.seeds.max(1) as usize)
+        .map(|runs| {
+            let n = runs.len() as f32;
+            Score {
+                average_wait: runs.iter().map(|r| r.average_wait).sum::<f32>() / n,
+                energy_kwh: runs.iter().map(|r| r.energy_kwh).sum::<f32>() / n,
+                max_wait: runs.iter().map(|r| r.max_wait).sum::<f32>() / n,
+            }
+        })
+        .collect()
+}
+
+// Number of other scores dominating each score, 0 means Pareto optimal
+fn domination_counts(scores: &[Score]) -> Vec<usize> {
+    scores
+        .iter()
+        .map(|s| scores.iter().filter(|other| other.dominates(s)).count())
+        .collect()
+}
+
+fn pareto_front(archive: &[(Candidate, Score)]) -> Vec<(Candidate, Score)> {
+    let scores: Vec<Score> = archive.iter().map(|(_, s)| *s).collect();
+    archive
+        .iter()
+        .zip(domination_counts(&scores))
+        .filter(|(_, count)| *count == 0)
+        .map(|(entry, _)| *entry)
+        .collect()
+}
+
+pub fn optimize(build: fn() -> Building, config: &OptimizerConfig) -> Vec<(Candidate, Score)> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut population: Vec<Candidate> = (0..config.population).map(|_| Candidate::random(&mut rng)).collect();
+    let mut archive = Vec::new();
+
+    for generation in 0..config.generations {
+        let scores = evaluate(build, &population, config);
+        archive.extend(population.iter().copied().zip(scores.iter().copied()));
+        println!("Generation {}: {} candidates on the Pareto front so far", generation + 1, pareto_front(&archive).len());
+
+        // Binary tournament on domination count, then uniform crossover and mutation
+        let counts = domination_counts(&scores);
+        let pick = |rng: &mut StdRng| {
+            let (a, b) = (rng.gen_range(0..population.len()), rng.gen_range(0..population.len()));
+            if counts[a] <= counts[b] { population[a] } else { population[b] }
+        };
+        population = (0..config.population)
+            .map(|_| {
+                let (first, second) = (pick(&mut rng), pick(&mut rng));
+                first.crossover(&second, &mut rng).mutate(&mut rng)
+            })
+            .collect();
+    }
+
+    let mut front = pareto_front(&archive);
+    front.sort_by(|a, b| a.1.average_wait.total_cmp(&b.1.average_wait));
+    front
+}
+
+pub fn run(build: fn() -> Building, config: &OptimizerConfig) {
+    println!(
+        "Optimizing over {} generations of {} candidates, {} seeds x {} steps each, {} threads",
+        config.generations, config.population, config.seeds, config.steps, config.threads
+    );
+    let front = optimize(build, config);
+    println!("--- Pareto front (average wait, energy, maximum wait) ---");
+    for (candidate, score) in front {
+        println!(
+            "wait {:.2} ride {:.2} parking {} idle {:>4.1}s -> avg wait {:.1}s, {:.3} kWh, max wait {:.1}s",
+            candidate.weights.wait,
+            candidate.weights.ride_delay,
+            if candidate.parking { "on " } else { "off" },
+            candidate.idle_timeout_s,
+            score.average_wait,
+            score.energy_kwh,
+            score.max_wait,
+        );
+    }
+}
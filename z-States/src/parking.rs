@@ -0,0 +1,42 @@
+// Parking strategies for idle cars. The control system asks the policy for a
+// home floor once a car has been standing empty for longer than the idle timeout.
+
+pub trait ParkingPolicy {
+    fn home_floor(&self, elevator_index: usize, num_elevators: usize, up_peak: bool) -> Option<i32>;
+    fn name(&self) -> &'static str;  // Identifies the policy in snapshots
+}
+
+// Old behaviour: idle cars stay wherever they stopped
+pub struct NoParking;
+
+impl ParkingPolicy for NoParking {
+    fn home_floor(&self, _elevator_index: usize, _num_elevators: usize, _up_peak: bool) -> Option<i32> {
+        None
+    }
+
+    fn name(&self) -> &'static str {
+        "none"
+    }
+}
+
+// Send every idle car to the lobby during up-peak, otherwise give each car
+// the middle floor of its own zone so the building is covered evenly
+pub struct LobbyZoneParking {
+    pub lobby: i32,
+    pub num_floors: i32,
+}
+
+impl ParkingPolicy for LobbyZoneParking {
+    fn home_floor(&self, elevator_index: usize, num_elevators: usize, up_peak: bool) -> Option<i32> {
+        if up_peak || num_elevators == 0 {
+            return Some(self.lobby);
+        }
+        let index = elevator_index as i32;
+        let count = num_elevators as i32;
+        Some((index * self.num_floors + self.num_floors / 2) / count)
+    }
+
+    fn name(&self) -> &'static str {
+        "lobby-zone"
+    }
+}
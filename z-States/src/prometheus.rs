@@ -0,0 +1,126 @@
+// Live metrics for soak runs, served over HTTP in the Prometheus text format.
+// The simulation calls `update` after every step; that renders a fresh page
+// from the control systems, and a background thread hands the latest page to
+// every scraper that connects.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::building::Building;
+use crate::tracker::StepTracker;
+
+const WAIT_BUCKETS: [f32; 9] = [2.0, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0, 300.0, 600.0];  // Upper bounds in seconds
+
+pub struct MetricsServer {
+    page: Arc<Mutex<String>>,
+    tracker: StepTracker,
+    wait_bucket_counts: [u64; WAIT_BUCKETS.len()],  // Not cumulative, summed up when rendering
+    wait_count: u64,
+    wait_sum: f64,
+}
+
+impl MetricsServer {
+    pub fn start(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let page = Arc::new(Mutex::new(String::new()));
+        let shared = Arc::clone(&page);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A broken connection only affects that scraper
+                let _ = respond(stream, &shared);
+            }
+        });
+        Ok(MetricsServer {
+            page,
+            tracker: StepTracker::default(),
+            wait_bucket_counts: [0; WAIT_BUCKETS.len()],
+            wait_count: 0,
+            wait_sum: 0.0,
+        })
+    }
+
+    pub fn update(&mut self, building: &Building) {
+        // Count only the waits recorded since the last update
+        for wait in self.tracker.update(building).iter().map(|b| b.wait_s) {
+            self.wait_count += 1;
+            self.wait_sum += wait as f64;
+            if let Some(bucket) = WAIT_BUCKETS.iter().position(|&bound| wait <= bound) {
+                self.wait_bucket_counts[bucket] += 1;
+            }
+        }
+
+        let elevators: Vec<_> = building.elevators().collect();
+        let waiting = building.waiting_per_floor();
+
+        let mut page = String::new();
+        metric(&mut page, "elevator_ticks_total", "counter", "Simulation steps run so far");
+        page += &format!("elevator_ticks_total {}\n", self.tracker.ticks);
+        metric(&mut page, "elevator_simulated_seconds_total", "counter", "Simulated time so far");
+        page += &format!("elevator_simulated_seconds_total {}\n", building.clock_seconds());
+
+        metric(&mut page, "elevator_passengers_waiting", "gauge", "Passengers waiting at a landing");
+        for (floor, count) in waiting.iter().enumerate() {
+            page += &format!("elevator_passengers_waiting{{floor=\"{}\"}} {}\n", floor, count);
+        }
+
+        let served: usize = elevators.iter().map(|e| e.passengers_served).sum();
+        metric(&mut page, "elevator_passengers_served_total", "counter", "Passengers delivered to their final floor");
+        page += &format!("elevator_passengers_served_total {}\n", served);
+
+        metric(&mut page, "elevator_wait_seconds", "histogram", "Wait from arrival until boarding the last leg");
+        let mut cumulative = 0;
+        for (bound, count) in WAIT_BUCKETS.iter().zip(&self.wait_bucket_counts) {
+            cumulative += count;
+            page += &format!("elevator_wait_seconds_bucket{{le=\"{}\"}} {}\n", bound, cumulative);
+        }
+        page += &format!("elevator_wait_seconds_bucket{{le=\"+Inf\"}} {}\n", self.wait_count);
+        page += &format!("elevator_wait_seconds_sum {}\n", self.wait_sum);
+        page += &format!("elevator_wait_seconds_count {}\n", self.wait_count);
+
+        metric(&mut page, "elevator_car_utilisation", "gauge", "Share of steps the car was not idle");
+        for (car, share) in self.tracker.utilisation().into_iter().enumerate() {
+            page += &format!("elevator_car_utilisation{{car=\"{}\"}} {:.4}\n", car, share);
+        }
+        metric(&mut page, "elevator_car_load_kg", "gauge", "Current load of the car");
+        for (car, elevator) in elevators.iter().enumerate() {
+            page += &format!("elevator_car_load_kg{{car=\"{}\"}} {:.1}\n", car, elevator.current_load_kg());
+        }
+        // Net energy can fall while a car regenerates, so each direction is a counter of its own
+        metric(&mut page, "elevator_car_energy_consumed_kwh_total", "counter", "Energy drawn by the car");
+        for (car, elevator) in elevators.iter().enumerate() {
+            page += &format!("elevator_car_energy_consumed_kwh_total{{car=\"{}\"}} {:.5}\n", car, elevator.energy.consumed_kwh());
+        }
+        metric(&mut page, "elevator_car_energy_regenerated_kwh_total", "counter", "Energy the car fed back while braking");
+        for (car, elevator) in elevators.iter().enumerate() {
+            page += &format!("elevator_car_energy_regenerated_kwh_total{{car=\"{}\"}} {:.5}\n", car, elevator.energy.regenerated_kwh());
+        }
+
+        *self.page.lock().unwrap() = page;
+    }
+}
+
+fn metric(page: &mut String, name: &str, kind: &str, help: &str) {
+    *page += &format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind);
+}
+
+// Any request gets the latest page, there is only one
+fn respond(mut stream: TcpStream, page: &Mutex<String>) -> io::Result<()> {
+    // A scraper that never finishes its request must not block the others
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line != "\r\n" && line != "\n" {
+        line.clear();
+    }
+    let body = page.lock().unwrap().clone();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
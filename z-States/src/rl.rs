@@ -0,0 +1,157 @@
+// Gym-like wrapper around a single-bank building for training dispatch policies.
+// The agent sees every hall call and every car as fixed-size numbers and decides
+// which car answers each call; the simulation itself is the same one the viewer runs.
+//
+// Observation layout, all values as f32:
+//   [0, 2 * NUM_FLOORS)      waiting passengers per hall call, slot = floor * 2 + (0 up, 1 down)
+//   then per car, 5 values   position / top floor, load / rated load,
+//                            direction (-1, 0, 1), doors open (0, 1), pending stops
+// Action: one car index per hall call slot, ignored for slots without waiting passengers.
+
+use std::io::{self, BufRead, Write};
+
+use crate::building::{Building, GroupTraffic};
+use crate::dispatch::Dispatcher;
+use crate::timing::Timing;
+use crate::{CabinState, DoorState, PassengerState, NUM_FLOORS};
+
+const CAR_FEATURES: usize = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub values: Vec<f32>,
+}
+
+pub type Action = Vec<usize>;
+
+// How each episode's building is set up; the preset must have a single bank
+pub struct RlConfig {
+    pub build: fn() -> Building,
+    pub timing: Timing,
+    pub groups: GroupTraffic,
+    pub episode_steps: usize,
+}
+
+pub struct RlEnv {
+    config: RlConfig,
+    building: Building,
+    num_elevators: usize,
+    steps_taken: usize,
+}
+
+impl RlEnv {
+    pub fn new(config: RlConfig) -> Self {
+        let building = RlEnv::build(&config, 0);
+        RlEnv {
+            num_elevators: building.banks[0].elevators.len(),
+            config,
+            building,
+            steps_taken: 0,
+        }
+    }
+
+    fn build(config: &RlConfig, seed: u64) -> Building {
+        let mut building = (config.build)().with_seed(seed);
+        building.set_timing(config.timing);
+        building.set_groups(config.groups);
+        building.set_dispatcher(Dispatcher::External);
+        building
+    }
+
+    pub fn num_hall_call_slots() -> usize {
+        NUM_FLOORS as usize * 2
+    }
+
+    pub fn observation_size(&self) -> usize {
+        RlEnv::num_hall_call_slots() + self.num_elevators * CAR_FEATURES
+    }
+
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.building = RlEnv::build(&self.config, seed);
+        self.steps_taken = 0;
+        self.observe()
+    }
+
+    pub fn step(&mut self, action: &Action) -> (Observation, f32, bool) {
+        let calls = self.hall_calls();
+        let bank = &mut self.building.banks[0];
+        for (slot, &waiting) in calls.iter().enumerate() {
+            if waiting > 0 {
+                if let Some(&car) = action.get(slot) {
+                    bank.assign_call(slot as i32 / 2, car);
+                }
+            }
+        }
+
+        self.building.step();
+        self.steps_taken += 1;
+
+        // Every waiting passenger costs one unit per step, so the return is minus the total wait
+        let reward = -(self.building.banks[0].passengers.len() as f32);
+        (self.observe(), reward, self.steps_taken >= self.config.episode_steps)
+    }
+
+    fn hall_calls(&self) -> Vec<usize> {
+        let mut calls = vec![0; RlEnv::num_hall_call_slots()];
+        for passenger in &self.building.banks[0].passengers {
+            if let PassengerState::Idle(floor) = passenger.state {
+                let slot = floor as usize * 2 + if passenger.destination > floor { 0 } else { 1 };
+                calls[slot] += 1;
+            }
+        }
+        calls
+    }
+
+    fn observe(&self) -> Observation {
+        let mut values: Vec<f32> = self.hall_calls().into_iter().map(|c| c as f32).collect();
+        let top_floor = (NUM_FLOORS - 1).max(1) as f32;
+        for elevator in &self.building.banks[0].elevators {
+            let direction = match elevator.cabin_state {
+                CabinState::Moving(from, to) if to > from => 1.0,
+                CabinState::Moving(from, to) if to < from => -1.0,
+                _ => 0.0,
+            };
+            values.push(elevator.position() as f32 / top_floor);
+            values.push(elevator.current_load_kg() / elevator.rated_load_kg);
+            values.push(direction);
+            values.push(if elevator.door_state == DoorState::Open { 1.0 } else { 0.0 });
+            values.push(elevator.destinations.len() as f32);
+        }
+        Observation { values }
+    }
+}
+
+fn format_values(values: &[f32]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+// Line protocol on stdin/stdout so agents in other languages can drive the environment:
+//   "reset SEED"      -> "OBS v0 v1 ..."
+//   "step A0 A1 ..."  -> "STEP REWARD DONE v0 v1 ..."
+//   "info"            -> "INFO OBSERVATION_SIZE NUM_SLOTS NUM_CARS"
+pub fn serve(config: RlConfig) {
+    let mut env = RlEnv::new(config);
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else { break };
+        let mut parts = line.split_whitespace();
+        let reply = match parts.next() {
+            Some("reset") => {
+                let seed = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+                format!("OBS {}", format_values(&env.reset(seed).values))
+            }
+            Some("step") => {
+                let action: Action = parts.filter_map(|s| s.parse().ok()).collect();
+                let (observation, reward, done) = env.step(&action);
+                format!("STEP {} {} {}", reward, done, format_values(&observation.values))
+            }
+            Some("info") => format!("INFO {} {} {}", env.observation_size(), RlEnv::num_hall_call_slots(), env.num_elevators),
+            Some("quit") => break,
+            _ => format!("ERROR unknown command '{}'", line),
+        };
+        if writeln!(stdout, "{}", reply).and_then(|_| stdout.flush()).is_err() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,30 @@
+// Two cars sharing one shaft (TWIN style). The lower car always stays at least
+// `min_separation` floors below the upper car; the motion planner holds a car
+// whose next floor would break that rule.
+
+#[derive(Debug, Clone)]
+pub struct Shaft {
+    pub lower: usize,  // Elevator indices within the bank
+    pub upper: usize,
+    pub min_separation: i32,
+}
+
+impl Shaft {
+    pub fn contains(&self, elevator_index: usize) -> bool {
+        self.lower == elevator_index || self.upper == elevator_index
+    }
+
+    // Floors the car can reach without its partner moving out of the way
+    pub fn free_range(&self, elevator_index: usize, positions: &[i32]) -> (i32, i32) {
+        if elevator_index == self.lower {
+            (i32::MIN, positions[self.upper] - self.min_separation)
+        } else {
+            (positions[self.lower] + self.min_separation, i32::MAX)
+        }
+    }
+
+    pub fn blocks(&self, elevator_index: usize, next_floor: i32, positions: &[i32]) -> bool {
+        let (low, high) = self.free_range(elevator_index, positions);
+        next_floor < low || next_floor > high
+    }
+}
@@ -0,0 +1,566 @@
+// Complete simulation state in a plain text file, one record per line: a
+// keyword followed by its values. Records after `bank` belong to that bank and
+// records after `car` to that car. Floats are written in their shortest exact
+// form and the random generator keeps its position, so a restored run goes on
+// exactly as the original would have.
+//
+//   building CLOCK_S PASSENGER_COUNTER NEXT_ARRIVAL ARRIVALS_PER_MINUTE ENTRANCE_SHARE GROUP_SHARE
+//   entrances FLOOR...
+//   rng SEED_HEX STREAM WORD_POS
+//   bank
+//   dispatcher nearest | eta WAIT RIDE_DELAY | external
+//   parking none | lobby-zone
+//   groups together | split
+//   timing TICK TRAVEL SETTLE OPENING OPEN CLOSING BOARDING IDLE_TIMEOUT MAX_WAIT
+//   transfer-floors FLOOR...
+//   shaft LOWER UPPER MIN_SEPARATION
+//   recent-origins FLOOR...
+//   car CABIN DOOR RATED_KG AREA_M2 BOARDING BYPASS DIRECTION MODE IDLE_S PHASE_S MOVED SERVED DOUBLE_DECK PRIORITY_STOP
+//   served-floors FLOOR...
+//   destinations FLOOR...
+//   assigned PASSENGER_ID PICKUP_STOP
+//   energy CAR_KG COUNTERWEIGHT_KG FLOOR_M MOTOR REGEN FRICTION_J DOOR_J STANDBY_W TRAVEL_J DOOR_J STANDBY_J REGENERATED_J
+//   rider | transferring | waiting | handoff  PASSENGER
+//   finished | trip  ID ORIGIN DESTINATION WAIT_S RIDE_S
+//   boarding ORIGIN ARRIVAL_S WAIT_S
+//   totals BOARDINGS TRIPS WAIT_SUM_S MAX_WAIT_S OVERDUE
+//
+// Only the latest boardings and trips are kept; `totals` counts all of them and
+// comes after the ones written.
+//
+// A passenger is ID STATE DIRECTION DESTINATION FINAL_DESTINATION MASS_KG LUGGAGE DECK WAITED_S RIDE_S ORIGIN ARRIVED_S OVERDUE GROUP,
+// with `-` for a missing final destination, priority stop or group.
+
+use std::fs;
+use std::io::{self, Write};
+use std::str::{FromStr, SplitWhitespace};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::building::Building;
+use crate::dispatch::{Dispatcher, EtaWeights};
+use crate::energy::{EnergyMeter, EnergyModel};
+use crate::parking::{LobbyZoneParking, NoParking};
+use crate::shaft::Shaft;
+use crate::timing::Timing;
+use crate::{
+    Boarding, CabinState, ControlSystem, Direction_, DoorState, Elevator, Luggage, Passenger, PassengerState, ServiceMode, Trip, WaitTotals,
+    LOBBY_FLOOR, NUM_FLOORS,
+};
+
+const HEADER: &str = "z-states-snapshot 6";
+
+fn join<T: ToString>(values: impl IntoIterator<Item = T>) -> String {
+    values.into_iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+fn cabin_text(state: &CabinState) -> String {
+    match state {
+        CabinState::Standing(f) => format!("standing:{}", f),
+        CabinState::Moving(from, to) => format!("moving:{}:{}", from, to),
+        CabinState::Holding(f) => format!("holding:{}", f),
+    }
+}
+
+fn door_text(state: &DoorState) -> &'static str {
+    match state {
+        DoorState::Closed => "closed",
+        DoorState::Opening => "opening",
+        DoorState::Open => "open",
+        DoorState::Closing => "closing",
+    }
+}
+
+fn direction_text(direction: &Direction_) -> &'static str {
+    match direction {
+        Direction_::UP => "up",
+        Direction_::DOWN => "down",
+    }
+}
+
+fn passenger_text(p: &Passenger) -> String {
+    let state = match p.state {
+        PassengerState::Idle(f) => format!("idle:{}", f),
+        PassengerState::Entering => "entering".to_string(),
+        PassengerState::ChoosingFloor => "choosing".to_string(),
+        PassengerState::InCabin => "in-cabin".to_string(),
+        PassengerState::Exiting => "exiting".to_string(),
+    };
+    let luggage = match p.luggage {
+        Luggage::None => "none",
+        Luggage::Suitcase => "suitcase",
+        Luggage::Trolley => "trolley",
+        Luggage::Wheelchair => "wheelchair",
+    };
+    format!(
+        "{} {} {} {} {} {} {} {} {} {} {} {} {} {}",
+        p.id,
+        state,
+        direction_text(&p.direction),
+        p.destination,
+        p.final_destination.map_or("-".to_string(), |f| f.to_string()),
+        p.mass_kg,
+        luggage,
+        p.deck,
+        p.waited_s,
+        p.ride_s,
+        p.origin,
+        p.arrived_s,
+        p.overdue,
+        p.group.map_or("-".to_string(), |g| g.to_string())
+    )
+}
+
+fn trip_text(t: &Trip) -> String {
+    format!("{} {} {} {} {}", t.id, t.origin, t.destination, t.wait_s, t.ride_s)
+}
+
+fn write_bank(out: &mut impl Write, bank: &ControlSystem) -> io::Result<()> {
+    writeln!(out, "bank")?;
+    match bank.dispatcher {
+        Dispatcher::Nearest => writeln!(out, "dispatcher nearest")?,
+        Dispatcher::Eta(w) => writeln!(out, "dispatcher eta {} {}", w.wait, w.ride_delay)?,
+        Dispatcher::External => writeln!(out, "dispatcher external")?,
+    }
+    writeln!(out, "parking {}", bank.parking_policy.name())?;
+    writeln!(out, "groups {}", if bank.split_groups { "split" } else { "together" })?;
+    let t = &bank.timing;
+    writeln!(
+        out,
+        "timing {} {} {} {} {} {} {} {} {}",
+        t.tick_seconds, t.travel_per_floor_s, t.settle_s, t.door_opening_s, t.door_open_s, t.door_closing_s, t.boarding_s, t.idle_timeout_s, t.max_wait_s
+    )?;
+    writeln!(out, "transfer-floors {}", join(&bank.transfer_floors))?;
+    for shaft in &bank.shafts {
+        writeln!(out, "shaft {} {} {}", shaft.lower, shaft.upper, shaft.min_separation)?;
+    }
+    writeln!(out, "recent-origins {}", join(&bank.recent_origins))?;
+
+    for e in &bank.elevators {
+        let mode = match e.service_mode {
+            ServiceMode::Group => "group",
+            ServiceMode::Independent => "independent",
+            ServiceMode::Attendant => "attendant",
+        };
+        writeln!(
+            out,
+            "car {} {} {} {} {} {} {} {} {} {} {} {} {} {}",
+            cabin_text(&e.cabin_state),
+            door_text(&e.door_state),
+            e.rated_load_kg,
+            e.floor_area_m2,
+            e.boarding_threshold,
+            e.bypass_threshold,
+            e.current_direction.as_ref().map_or("-", direction_text),
+            mode,
+            e.idle_s,
+            e.phase_s,
+            e.moved_this_stop,
+            e.passengers_served,
+            e.double_deck,
+            e.priority_stop.map_or("-".to_string(), |f| f.to_string())
+        )?;
+        writeln!(out, "served-floors {}", join(&e.served_floors))?;
+        writeln!(out, "destinations {}", join(&e.destinations))?;
+        for (id, stop) in &e.assigned_calls {
+            writeln!(out, "assigned {} {}", id, stop)?;
+        }
+        let (m, meter) = (&e.energy.model, &e.energy);
+        writeln!(
+            out,
+            "energy {} {} {} {} {} {} {} {} {} {} {} {}",
+            m.car_mass_kg,
+            m.counterweight_kg,
+            m.floor_height_m,
+            m.motor_efficiency,
+            m.regen_efficiency,
+            m.friction_j_per_floor,
+            m.door_operation_j,
+            m.standby_w,
+            meter.travel_j,
+            meter.door_j,
+            meter.standby_j,
+            meter.regenerated_j
+        )?;
+        for p in &e.passengers {
+            writeln!(out, "rider {}", passenger_text(p))?;
+        }
+        for p in &e.transferring {
+            writeln!(out, "transferring {}", passenger_text(p))?;
+        }
+        for t in &e.finished_trips {
+            writeln!(out, "finished {}", trip_text(t))?;
+        }
+    }
+
+    for p in &bank.passengers {
+        writeln!(out, "waiting {}", passenger_text(p))?;
+    }
+    for p in &bank.handoffs {
+        writeln!(out, "handoff {}", passenger_text(p))?;
+    }
+    for b in bank.boardings.after(0) {
+        writeln!(out, "boarding {} {} {}", b.origin, b.arrival_s, b.wait_s)?;
+    }
+    for t in bank.trips.after(0) {
+        writeln!(out, "trip {}", trip_text(t))?;
+    }
+    let waits = &bank.waits;
+    writeln!(out, "totals {} {} {} {} {}", bank.boardings.total, bank.trips.total, waits.wait_sum_s, waits.max_wait_s, waits.overdue)?;
+    Ok(())
+}
+
+fn write_building(out: &mut impl Write, building: &Building) -> io::Result<()> {
+    writeln!(out, "{}", HEADER)?;
+    writeln!(
+        out,
+        "building {} {} {} {} {} {}",
+        building.clock_s,
+        building.passenger_counter,
+        building.next_arrival.map_or("-".to_string(), |n| n.to_string()),
+        building.arrivals_per_minute,
+        building.entrance_share,
+        building.group_share
+    )?;
+    writeln!(out, "entrances {}", join(&building.entrances))?;
+    let seed: String = building.rng.get_seed().iter().map(|b| format!("{:02x}", b)).collect();
+    writeln!(out, "rng {} {} {}", seed, building.rng.get_stream(), building.rng.get_word_pos())?;
+    for bank in &building.banks {
+        write_bank(out, bank)?;
+    }
+    Ok(())
+}
+
+// The whole snapshot as it would be saved
+pub fn text(building: &Building) -> String {
+    let mut out = Vec::new();
+    write_building(&mut out, building).expect("writing to memory cannot fail");
+    String::from_utf8(out).expect("snapshots are plain text")
+}
+
+pub fn write(building: &Building, path: &str) -> io::Result<()> {
+    fs::write(path, text(building))
+}
+
+pub fn save(building: &Building, path: &str) {
+    match write(building, path) {
+        Ok(()) => println!("Saved snapshot at {:.0}s to {}", building.clock_seconds(), path),
+        Err(e) => println!("Snapshot to {} failed: {}", path, e),
+    }
+}
+
+// Values of one record, read front to back
+struct Values<'a>(SplitWhitespace<'a>);
+
+impl<'a> Values<'a> {
+    fn text(&mut self) -> Result<&'a str, String> {
+        self.0.next().ok_or_else(|| "missing value".to_string())
+    }
+
+    fn next<T: FromStr>(&mut self) -> Result<T, String> {
+        let text = self.text()?;
+        text.parse().map_err(|_| format!("invalid value '{}'", text))
+    }
+
+    // `-` stands for nothing
+    fn optional<T: FromStr>(&mut self) -> Result<Option<T>, String> {
+        match self.text()? {
+            "-" => Ok(None),
+            text => text.parse().map(Some).map_err(|_| format!("invalid value '{}'", text)),
+        }
+    }
+
+    fn rest<T: FromStr>(&mut self) -> Result<Vec<T>, String> {
+        self.0.by_ref().map(|text| text.parse().map_err(|_| format!("invalid value '{}'", text))).collect()
+    }
+}
+
+fn parse_floors(text: &str, parts: usize) -> Result<Vec<i32>, String> {
+    let floors: Vec<i32> = text.split(':').skip(1).map(|f| f.parse()).collect::<Result<_, _>>().map_err(|_| format!("invalid state '{}'", text))?;
+    if floors.len() != parts {
+        return Err(format!("invalid state '{}'", text));
+    }
+    Ok(floors)
+}
+
+fn parse_cabin(text: &str) -> Result<CabinState, String> {
+    match text.split(':').next() {
+        Some("standing") => Ok(CabinState::Standing(parse_floors(text, 1)?[0])),
+        Some("holding") => Ok(CabinState::Holding(parse_floors(text, 1)?[0])),
+        Some("moving") => {
+            let floors = parse_floors(text, 2)?;
+            Ok(CabinState::Moving(floors[0], floors[1]))
+        }
+        _ => Err(format!("unknown cabin state '{}'", text)),
+    }
+}
+
+fn parse_door(text: &str) -> Result<DoorState, String> {
+    match text {
+        "closed" => Ok(DoorState::Closed),
+        "opening" => Ok(DoorState::Opening),
+        "open" => Ok(DoorState::Open),
+        "closing" => Ok(DoorState::Closing),
+        _ => Err(format!("unknown door state '{}'", text)),
+    }
+}
+
+fn parse_direction(text: &str) -> Result<Direction_, String> {
+    match text {
+        "up" => Ok(Direction_::UP),
+        "down" => Ok(Direction_::DOWN),
+        _ => Err(format!("unknown direction '{}'", text)),
+    }
+}
+
+fn parse_passenger(values: &mut Values) -> Result<Passenger, String> {
+    let id = values.next()?;
+    let state = match values.text()? {
+        "entering" => PassengerState::Entering,
+        "choosing" => PassengerState::ChoosingFloor,
+        "in-cabin" => PassengerState::InCabin,
+        "exiting" => PassengerState::Exiting,
+        text if text.starts_with("idle:") => PassengerState::Idle(parse_floors(text, 1)?[0]),
+        text => return Err(format!("unknown passenger state '{}'", text)),
+    };
+    let direction = parse_direction(values.text()?)?;
+    let destination = values.next()?;
+    let final_destination = values.optional()?;
+    let mass_kg = values.next()?;
+    let luggage = match values.text()? {
+        "none" => Luggage::None,
+        "suitcase" => Luggage::Suitcase,
+        "trolley" => Luggage::Trolley,
+        "wheelchair" => Luggage::Wheelchair,
+        text => return Err(format!("unknown luggage '{}'", text)),
+    };
+    Ok(Passenger {
+        id,
+        state,
+        direction,
+        destination,
+        final_destination,
+        mass_kg,
+        luggage,
+        deck: values.next()?,
+        waited_s: values.next()?,
+        ride_s: values.next()?,
+        origin: values.next()?,
+        arrived_s: values.next()?,
+        overdue: values.next()?,
+        group: values.optional()?,
+    })
+}
+
+fn parse_trip(values: &mut Values) -> Result<Trip, String> {
+    Ok(Trip { id: values.next()?, origin: values.next()?, destination: values.next()?, wait_s: values.next()?, ride_s: values.next()? })
+}
+
+fn parse_rng(values: &mut Values) -> Result<ChaCha12Rng, String> {
+    let hex = values.text()?;
+    let mut seed = [0u8; 32];
+    if hex.len() != 64 {
+        return Err("random seed must be 64 hex digits".to_string());
+    }
+    for (i, byte) in seed.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| format!("invalid random seed '{}'", hex))?;
+    }
+    let mut rng = ChaCha12Rng::from_seed(seed);
+    rng.set_stream(values.next()?);
+    rng.set_word_pos(values.next()?);
+    Ok(rng)
+}
+
+fn parse_car(values: &mut Values, timing: Timing) -> Result<Elevator, String> {
+    let mut car = Elevator::new(0);
+    car.cabin_state = parse_cabin(values.text()?)?;
+    car.door_state = parse_door(values.text()?)?;
+    car.rated_load_kg = values.next()?;
+    car.floor_area_m2 = values.next()?;
+    car.boarding_threshold = values.next()?;
+    car.bypass_threshold = values.next()?;
+    car.current_direction = match values.text()? {
+        "-" => None,
+        text => Some(parse_direction(text)?),
+    };
+    car.service_mode = match values.text()? {
+        "group" => ServiceMode::Group,
+        "independent" => ServiceMode::Independent,
+        "attendant" => ServiceMode::Attendant,
+        text => return Err(format!("unknown service mode '{}'", text)),
+    };
+    car.idle_s = values.next()?;
+    car.phase_s = values.next()?;
+    car.moved_this_stop = values.next()?;
+    car.passengers_served = values.next()?;
+    car.double_deck = values.next()?;
+    car.priority_stop = values.optional()?;
+    car.timing = timing;
+    Ok(car)
+}
+
+fn parse_energy(values: &mut Values) -> Result<EnergyMeter, String> {
+    let model = EnergyModel {
+        car_mass_kg: values.next()?,
+        counterweight_kg: values.next()?,
+        floor_height_m: values.next()?,
+        motor_efficiency: values.next()?,
+        regen_efficiency: values.next()?,
+        friction_j_per_floor: values.next()?,
+        door_operation_j: values.next()?,
+        standby_w: values.next()?,
+    };
+    Ok(EnergyMeter {
+        model,
+        travel_j: values.next()?,
+        door_j: values.next()?,
+        standby_j: values.next()?,
+        regenerated_j: values.next()?,
+    })
+}
+
+// Applies one record to the building being restored
+fn apply_record(building: &mut Building, keyword: &str, values: &mut Values) -> Result<(), String> {
+    if keyword == "bank" {
+        building.banks.push(ControlSystem::with_elevators(Vec::new()));
+        return Ok(());
+    }
+    if let Some(result) = apply_building_record(building, keyword, values) {
+        return result;
+    }
+    let bank = building.banks.last_mut().ok_or_else(|| format!("'{}' before the first bank", keyword))?;
+    match keyword {
+        "dispatcher" => {
+            bank.dispatcher = match values.text()? {
+                "nearest" => Dispatcher::Nearest,
+                "eta" => Dispatcher::Eta(EtaWeights { wait: values.next()?, ride_delay: values.next()? }),
+                "external" => Dispatcher::External,
+                text => return Err(format!("unknown dispatcher '{}'", text)),
+            }
+        }
+        "parking" => match values.text()? {
+            "none" => bank.set_parking_policy(Box::new(NoParking)),
+            "lobby-zone" => bank.set_parking_policy(Box::new(LobbyZoneParking { lobby: LOBBY_FLOOR, num_floors: NUM_FLOORS })),
+            text => return Err(format!("unknown parking policy '{}'", text)),
+        },
+        "timing" => bank.set_timing(Timing {
+            tick_seconds: values.next()?,
+            travel_per_floor_s: values.next()?,
+            settle_s: values.next()?,
+            door_opening_s: values.next()?,
+            door_open_s: values.next()?,
+            door_closing_s: values.next()?,
+            boarding_s: values.next()?,
+            idle_timeout_s: values.next()?,
+            max_wait_s: values.next()?,
+        }),
+        "groups" => {
+            bank.split_groups = match values.text()? {
+                "together" => false,
+                "split" => true,
+                text => return Err(format!("unknown group boarding '{}'", text)),
+            }
+        }
+        "transfer-floors" => bank.transfer_floors = values.rest()?,
+        "shaft" => bank.shafts.push(Shaft { lower: values.next()?, upper: values.next()?, min_separation: values.next()? }),
+        "recent-origins" => bank.recent_origins = values.rest::<i32>()?.into_iter().collect(),
+        "car" => {
+            let car = parse_car(values, bank.timing)?;
+            bank.elevators.push(car);
+        }
+        "waiting" => bank.passengers.push(parse_passenger(values)?),
+        "handoff" => bank.handoffs.push(parse_passenger(values)?),
+        "boarding" => bank.boardings.push(Boarding { origin: values.next()?, arrival_s: values.next()?, wait_s: values.next()? }),
+        "trip" => bank.trips.push(parse_trip(values)?),
+        "totals" => {
+            bank.boardings.total = values.next()?;
+            bank.trips.total = values.next()?;
+            bank.waits = WaitTotals { wait_sum_s: values.next()?, max_wait_s: values.next()?, overdue: values.next()? };
+        }
+        _ => {
+            let car = bank.elevators.last_mut().ok_or_else(|| format!("'{}' before the first car", keyword))?;
+            match keyword {
+                "served-floors" => car.served_floors = values.rest::<i32>()?.into_iter().collect(),
+                "destinations" => car.destinations = values.rest::<i32>()?.into_iter().collect(),
+                "assigned" => car.assigned_calls.push((values.next()?, values.next()?)),
+                "energy" => car.energy = parse_energy(values)?,
+                "rider" => car.passengers.push(parse_passenger(values)?),
+                "transferring" => car.transferring.push(parse_passenger(values)?),
+                "finished" => car.finished_trips.push(parse_trip(values)?),
+                _ => return Err(format!("unknown record '{}'", keyword)),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_building(building: &mut Building, values: &mut Values) -> Result<(), String> {
+    building.clock_s = values.next()?;
+    building.passenger_counter = values.next()?;
+    building.next_arrival = values.optional()?;
+    building.arrivals_per_minute = values.next()?;
+    building.entrance_share = values.next()?;
+    building.group_share = values.next()?;
+    Ok(())
+}
+
+// Records about the building as a whole, None for any other keyword
+fn apply_building_record(building: &mut Building, keyword: &str, values: &mut Values) -> Option<Result<(), String>> {
+    let result = match keyword {
+        "building" => parse_building(building, values),
+        "entrances" => values.rest().map(|floors| building.entrances = floors),
+        "rng" => parse_rng(values).map(|rng| building.rng = rng),
+        _ => return None,
+    };
+    Some(result)
+}
+
+pub fn load(path: &str) -> Result<Building, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    parse(&text, path)
+}
+
+// Rebuilds a building from snapshot text; `source` names it in error messages
+pub fn parse(text: &str, source: &str) -> Result<Building, String> {
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(format!("{} is not a snapshot in the current format", source));
+    }
+    let mut building = Building::new(Vec::new(), Vec::new(), 0.0);
+    for (number, line) in lines.enumerate() {
+        let mut values = Values(line.split_whitespace());
+        let Some(keyword) = values.0.next() else {
+            continue;
+        };
+        apply_record(&mut building, keyword, &mut values).map_err(|e| format!("{} line {}: {}", source, number + 2, e))?;
+    }
+    if building.banks.is_empty() {
+        return Err(format!("{} has no elevator banks", source));
+    }
+    Ok(building)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::harness::Scenario;
+
+    #[test]
+    fn loaded_snapshot_continues_like_the_original() {
+        crate::QUIET.store(true, std::sync::atomic::Ordering::Relaxed);
+        for seed in 0..30 {
+            let scenario = Scenario::random(seed);
+            let (saved_at, more) = (scenario.steps.min(500), 300);
+            let mut original = scenario.building();
+            for _ in 0..saved_at {
+                original.step();
+            }
+            let mut loaded = super::parse(&super::text(&original), "snapshot").unwrap();
+            for _ in 0..more {
+                original.step();
+                loaded.step();
+            }
+            assert!(super::text(&original) == super::text(&loaded), "loaded run differs: {}", scenario);
+        }
+    }
+}
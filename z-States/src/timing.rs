@@ -0,0 +1,57 @@
+// Durations of everything a car does, in simulated seconds. A car stays in each
+// phase of its state machine until the phase has lasted its duration, so the
+// tick length only sets the time resolution: with the defaults every phase lasts
+// exactly one 1.5 s tick, which is how the simulator has always behaved.
+
+use crate::{CabinState, DoorState};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timing {
+    pub tick_seconds: f32,        // Simulated time one step stands for
+    pub travel_per_floor_s: f32,  // Moving from one floor to the next
+    pub settle_s: f32,            // Standing at a floor, levelling before the doors open or before moving on
+    pub door_opening_s: f32,
+    pub door_open_s: f32,         // Shortest time the doors stay fully open
+    pub door_closing_s: f32,
+    pub boarding_s: f32,          // Extra open time for every passenger entering or leaving
+    pub idle_timeout_s: f32,      // Standing empty this long sends a car to its home floor
+    pub max_wait_s: f32,          // Hall calls waiting this long are escalated
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Timing {
+            tick_seconds: 1.5,
+            travel_per_floor_s: 1.5,
+            settle_s: 1.5,
+            door_opening_s: 1.5,
+            door_open_s: 1.5,
+            door_closing_s: 1.5,
+            boarding_s: 0.0,
+            idle_timeout_s: 7.5,
+            max_wait_s: 120.0,
+        }
+    }
+}
+
+impl Timing {
+    // How long the car must stay in its current phase before moving on
+    pub fn phase_seconds(&self, cabin: &CabinState, door: &DoorState, passengers_moved: u32) -> f32 {
+        match (cabin, door) {
+            (CabinState::Moving(_, _), _) => self.travel_per_floor_s,
+            (CabinState::Holding(_), DoorState::Opening) => self.door_opening_s,
+            (CabinState::Holding(_), DoorState::Open) => self.door_open_s + self.boarding_s * passengers_moved as f32,
+            (CabinState::Holding(_), DoorState::Closing) => self.door_closing_s,
+            _ => self.settle_s,
+        }
+    }
+
+    // Seconds per floor travelled and per stop made, used to estimate arrival times
+    pub fn seconds_per_floor(&self) -> f32 {
+        self.travel_per_floor_s + self.settle_s
+    }
+
+    pub fn seconds_per_stop(&self) -> f32 {
+        self.settle_s + self.door_opening_s + self.door_open_s + self.door_closing_s
+    }
+}
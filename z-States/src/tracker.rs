@@ -0,0 +1,39 @@
+// Follows a building from one update to the next for the live views: how many
+// updates each car was busy for, and which boardings are new since the last one.
+
+use crate::building::Building;
+use crate::Boarding;
+
+#[derive(Default)]
+pub struct StepTracker {
+    pub ticks: u64,
+    pub busy_ticks: Vec<u64>,  // Per car
+    seen: Vec<u64>,            // Per bank, entries of `boardings` already handed out
+}
+
+impl StepTracker {
+    // Counts one more tick and returns the boardings recorded since the last call
+    pub fn update(&mut self, building: &Building) -> Vec<Boarding> {
+        self.ticks += 1;
+        let elevators: Vec<_> = building.elevators().collect();
+        self.busy_ticks.resize(elevators.len(), 0);
+        for (busy, elevator) in self.busy_ticks.iter_mut().zip(&elevators) {
+            if !elevator.is_idle() {
+                *busy += 1;
+            }
+        }
+
+        self.seen.resize(building.banks.len(), 0);
+        let mut boarded = Vec::new();
+        for (bank, seen) in building.banks.iter().zip(self.seen.iter_mut()) {
+            boarded.extend(bank.boardings.after(*seen).copied());
+            *seen = bank.boardings.total;
+        }
+        boarded
+    }
+
+    // Share of ticks each car was not idle
+    pub fn utilisation(&self) -> Vec<f64> {
+        self.busy_ticks.iter().map(|&busy| if self.ticks > 0 { busy as f64 / self.ticks as f64 } else { 0.0 }).collect()
+    }
+}
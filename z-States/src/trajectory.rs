@@ -0,0 +1,115 @@
+// Space-time diagram of a recorded run: time runs to the right, floors go up,
+// every car is a line through its recorded cabin states with a thick band
+// wherever its doors were not closed. Hall calls show as an open circle where
+// the passenger arrived, joined by a dashed line to a dot where a car took them.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::metrics::Recorder;
+use crate::{DoorState, NUM_FLOORS};
+
+const CAR_COLORS: [&str; 6] = ["#1f77b4", "#2ca02c", "#9467bd", "#8c564b", "#17becf", "#bcbd22"];
+const FLOOR_HEIGHT: f32 = 80.0;
+const LEFT: f32 = 70.0;
+const TOP: f32 = 40.0;
+
+pub fn write_svg(recorder: &Recorder, path: &str) -> io::Result<()> {
+    let ticks = recorder.ticks.len();
+    let tick_width = (1600.0 / ticks.max(1) as f32).clamp(0.5, 12.0);
+    let plot_width = ticks as f32 * tick_width;
+    let plot_height = (NUM_FLOORS - 1) as f32 * FLOOR_HEIGHT;
+    let x = |tick: usize| LEFT + (tick - 1) as f32 * tick_width;  // Ticks are recorded from 1
+    let y = |floor: i32| TOP + plot_height - floor as f32 * FLOOR_HEIGHT;
+    let y_at = |height: f32| TOP + plot_height - height * FLOOR_HEIGHT;
+
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"12\">",
+        LEFT + plot_width + 30.0,
+        TOP + plot_height + 60.0
+    )?;
+    let end_s = recorder.ticks.last().map_or(0.0, |t| t.time_s);
+    writeln!(out, "<text x=\"{}\" y=\"20\" font-size=\"14\">Car trajectories over {} ticks ({:.0}s)</text>", LEFT, ticks, end_s)?;
+
+    // Floor grid and time axis
+    for floor in 0..NUM_FLOORS {
+        writeln!(out, "<line x1=\"{}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" stroke=\"#ddd\"/>", LEFT, LEFT + plot_width, y = y(floor))?;
+        writeln!(out, "<text x=\"10\" y=\"{}\">Floor {}</text>", y(floor) + 4.0, floor)?;
+    }
+    let label_every = ((60.0 / tick_width).ceil() as usize).max(1);
+    for sample in recorder.ticks.iter().step_by(label_every) {
+        writeln!(out, "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{:.0}s</text>", x(sample.tick), TOP + plot_height + 25.0, sample.time_s)?;
+    }
+
+    let num_cars = recorder.ticks.first().map_or(0, |t| t.cars.len());
+    for car in 0..num_cars {
+        let color = CAR_COLORS[car % CAR_COLORS.len()];
+
+        // Where the car is at each tick, moving one floor per travel phase however many ticks that takes
+        let mut path_data = String::new();
+        for sample in &recorder.ticks {
+            let command = if path_data.is_empty() { 'M' } else { 'L' };
+            path_data += &format!("{}{:.1},{:.1} ", command, x(sample.tick), y_at(sample.cars[car].height()));
+        }
+        if let Some(last) = recorder.ticks.last() {
+            path_data += &format!("L{:.1},{:.1}", x(last.tick) + tick_width, y_at(last.cars[car].height()));
+        }
+        writeln!(out, "<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"/>", path_data.trim_end(), color)?;
+
+        // Door segments, merged over consecutive ticks at the same floor
+        let mut segments = Vec::new();
+        let mut open_since: Option<(usize, i32)> = None;
+        for sample in &recorder.ticks {
+            let c = &sample.cars[car];
+            let open = (c.door != DoorState::Closed).then(|| c.position());
+            if let Some((start, floor)) = open_since {
+                if open != Some(floor) {
+                    segments.push((start, sample.tick, floor));
+                    open_since = None;
+                }
+            }
+            if open_since.is_none() {
+                open_since = open.map(|floor| (sample.tick, floor));
+            }
+        }
+        if let Some((start, floor)) = open_since {
+            segments.push((start, ticks + 1, floor));
+        }
+        for (start, end, floor) in segments {
+            writeln!(
+                out,
+                "<line x1=\"{:.1}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\" stroke=\"{}\" stroke-width=\"7\" stroke-opacity=\"0.5\"/>",
+                x(start),
+                x(end),
+                color,
+                y = y(floor)
+            )?;
+        }
+        writeln!(out, "<text x=\"{}\" y=\"{}\" fill=\"{}\">E{}</text>", LEFT + car as f32 * 40.0, TOP + plot_height + 48.0, color, car)?;
+    }
+
+    // Hall calls: arrival, wait and pickup
+    for call in &recorder.hall_calls {
+        let (ax, cy) = (x(call.arrival_tick), y(call.floor));
+        writeln!(out, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"none\" stroke=\"red\"/>", ax, cy)?;
+        if let Some(served) = call.served_tick {
+            writeln!(out, "<line x1=\"{:.1}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\" stroke=\"red\" stroke-dasharray=\"2,2\"/>", ax, x(served), y = cy)?;
+            writeln!(out, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"red\"/>", x(served), cy)?;
+        }
+    }
+    writeln!(out, "</svg>")?;
+    out.flush()
+}
+
+pub fn export(recorder: &Recorder, path: &str) {
+    if recorder.ticks.is_empty() {
+        println!("Nothing recorded yet, no trajectory diagram written");
+        return;
+    }
+    match write_svg(recorder, path) {
+        Ok(()) => println!("Wrote trajectory diagram to {}", path),
+        Err(e) => println!("Trajectory export to {} failed: {}", path, e),
+    }
+}